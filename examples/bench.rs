@@ -1,9 +1,16 @@
 use ark_bls12_381::Fr;
-use ark_ff::PrimeField;
+use ark_ff::{BigInteger, Field, One, PrimeField};
 use rand::Rng;
+use std::collections::BinaryHeap;
+use std::time::Instant;
 use vector_addition_chain::{
-    bos_coster, bos_coster_fast, bos_coster_many, VecAddChain,
+    bos_coster, bos_coster_fast, bos_coster_many, bos_coster_signed, bounds, chunked, complete,
+    depth, fixed_base, glv, optimize, pippenger,
+    scalar::{ArkBigInt, Scalar},
+    straus, stream, VecAddChain,
 };
+#[cfg(feature = "r1cs")]
+use vector_addition_chain::r1cs;
 
 use clap::arg_enum;
 use structopt::StructOpt;
@@ -15,6 +22,10 @@ arg_enum! {
         Deep,
         ManyShallow,
         ManyDeep,
+        ManyPar,
+        Signed,
+        Pippenger,
+        Straus,
     }
 }
 
@@ -25,43 +36,312 @@ struct Opt {
     #[structopt(short = "a", long = "alg", default_value = "fast")]
     alg: Alg,
 
+    /// Window for Pippenger or Straus (default: automatic)
+    #[structopt(short = "w", long = "window")]
+    window: Option<usize>,
+
+    /// Bound the chain's depth, ignoring the algorithm, and report the additions-versus-depth tradeoff
+    #[structopt(short = "d", long = "max-depth")]
+    max_depth: Option<usize>,
+
+    /// Make the chain complete, and report the cost of doing so
+    #[structopt(long = "complete")]
+    complete: bool,
+
+    /// Precompute each base times 2^k for every k a multiple of this, and report the adds it saves
+    #[structopt(long = "fixed-base")]
+    fixed_base: Option<usize>,
+
+    /// Split the scalars with the BLS12-381 G1 endomorphism, and report the adds it saves
+    #[structopt(long = "glv")]
+    glv: bool,
+
+    /// Stream a Fast chain to this file, without holding it in memory, and report its length
+    #[structopt(long = "spill")]
+    spill: Option<std::path::PathBuf>,
+
+    /// Time the Fast builder's reduction against the same one on std's BinaryHeap, without keeping
+    /// either chain
+    #[structopt(long = "time-heap")]
+    time_heap: bool,
+
+    /// Time ManyPar on 1, 2, 4, ... up to this many threads, against Fast, and report the adds
+    #[structopt(long = "threads")]
+    threads: Option<usize>,
+
+    /// Chart additions per element and build time against chunk size, halving it this many times
+    #[structopt(long = "chunks")]
+    chunks: Option<usize>,
+
     /// Size
     #[structopt()]
     size: usize,
 }
 
-fn test<F: PrimeField, R: Rng>(elems: usize, alg: Alg, rng: &mut R) {
+fn test<F: PrimeField, R: Rng>(opt: &Opt, rng: &mut R, glv: &glv::Glv<F>) {
+    let elems = opt.size;
+    let alg = &opt.alg;
     //let builder = bos_coster::build_chain::<Fr, bos_coster::UseShallow>;
     let target = (0..elems).map(|_| F::rand(rng)).collect::<Vec<F>>();
-    let builder: Box<dyn Fn(Vec<F>) -> VecAddChain> = match alg {
+    if let Some(path) = &opt.spill {
+        let scalars = target.iter().map(|f| ArkBigInt(f.into_repr())).collect();
+        let adds = stream::spill_chain(scalars, path).expect("cannot write the chain");
+        println!(
+            "Spilled, elems: {:>8}, Adds per elem: {:>8.1}",
+            elems,
+            adds as f64 / elems as f64
+        );
+        return;
+    }
+    if opt.time_heap {
+        // time only the reduction, in both: finalizing is the same whatever the heap
+        let scalars: Vec<_> = target.iter().map(|f| ArkBigInt(f.into_repr())).collect();
+        let start = Instant::now();
+        let mut steps = bos_coster_fast::Steps::new(scalars.clone());
+        let mut adds = 0;
+        while steps.remaining() > 1 {
+            steps.next();
+            adds += 1;
+        }
+        let fast = start.elapsed();
+        let start = Instant::now();
+        let reference_adds = binary_heap_adds(scalars);
+        let reference = start.elapsed();
+        println!(
+            "Heap, elems: {:>8}, Adds per elem: {:>8.1}, Fast: {:>8.3}s, BinaryHeap: {:>8.3}s, speedup: {:>6.2}",
+            elems,
+            adds as f64 / elems as f64,
+            fast.as_secs_f64(),
+            reference.as_secs_f64(),
+            reference.as_secs_f64() / fast.as_secs_f64(),
+        );
+        assert_eq!(reference_adds, adds);
+        return;
+    }
+    if let Some(max_threads) = opt.threads {
+        time_threads(target, max_threads);
+        return;
+    }
+    let builder: Box<dyn Fn(Vec<F>) -> VecAddChain + Sync> = match alg {
         Alg::Shallow => Box::new(bos_coster::build_chain::<F, bos_coster::UseShallow>),
         Alg::Deep => Box::new(bos_coster::build_chain::<F, bos_coster::UseDeep>),
         Alg::ManyShallow => Box::new(bos_coster_many::build_chain::<F, bos_coster::UseShallow>),
         Alg::ManyDeep => Box::new(bos_coster_many::build_chain::<F, bos_coster::UseDeep>),
+        #[cfg(feature = "parallel")]
+        Alg::ManyPar => Box::new(vector_addition_chain::bos_coster_par::build_chain::<F>),
+        #[cfg(not(feature = "parallel"))]
+        Alg::ManyPar => panic!("ManyPar needs the `parallel` feature"),
         Alg::Fast => Box::new(bos_coster_fast::build_chain::<F>),
+        Alg::Signed => Box::new(bos_coster_signed::build_chain::<F>),
+        Alg::Pippenger => match opt.window {
+            Some(w) => Box::new(move |t| pippenger::build_chain::<F>(t, w)),
+            None => Box::new(pippenger::build_chain_auto::<F>),
+        },
+        Alg::Straus => match opt.window {
+            Some(w) => Box::new(move |t| straus::build_chain::<F>(t, w)),
+            None => Box::new(straus::build_chain_auto::<F>),
+        },
     };
-    let chain = builder(target.clone());
+    let start = Instant::now();
+    let mut chain = match opt.max_depth {
+        Some(max_depth) => {
+            let widths = depth::widths(F::size_in_bits()).into_iter().map(Some);
+            for (width, chain) in widths.chain(Some(None)).zip(depth::tradeoff(&target)) {
+                let name = width.map_or("low-depth".to_string(), |w| format!("width {}", w));
+                println!(
+                    "{:>10}: adds {:>8}, depth {:>8}",
+                    name,
+                    chain.adds.len(),
+                    chain.depth()
+                );
+            }
+            depth::build_chain(target.clone(), max_depth).expect("no chain within the depth bound")
+        }
+        None => builder(target.clone()),
+    };
+    let build_time = start.elapsed();
     //check_chain(&chain, &target);
+    if opt.complete {
+        let complete = complete::make_complete::<F>(&chain);
+        println!(
+            "Complete: {} new doublings, {} removed adds, {} doublings, {:+} adds",
+            complete.new_doublings,
+            complete.removed,
+            complete.doubling_steps().len(),
+            complete.extra_adds(),
+        );
+        chain = complete.chain;
+    }
     let adds = chain.adds.len();
     let ops_per_elem = adds as f64 / elems as f64;
-    let add_cost = 6f64;
     let field_size = <F as PrimeField>::size_in_bits() as f64;
-    let cs_per_elem = ops_per_elem * add_cost;
+    // Measured on ed-on-bls12-381 when the `r1cs` feature is on; otherwise estimated.
+    #[cfg(feature = "r1cs")]
+    let cs_per_elem = {
+        use ark_ed_on_bls12_381::{constraints::EdwardsVar, EdwardsProjective, Fq};
+        let cs = r1cs::count_constraints::<EdwardsProjective, Fq, EdwardsVar>(&chain)
+            .expect("synthesis failed");
+        cs as f64 / elems as f64
+    };
+    #[cfg(not(feature = "r1cs"))]
+    let cs_per_elem = ops_per_elem * 6f64;
     let cs_per_bit = cs_per_elem / field_size;
     println!(
-        "{:10?}, elems: {:>8}, Adds per elem: {:>8.1}, Cs per elem: {:>8.2}, Cs per bit: {:>8.3}",
+        "{:10?}, elems: {:>8}, Adds per elem: {:>8.1}, Cs per elem: {:>8.2}, Cs per bit: {:>8.3}, Depth: {:>8}, Time: {:>8.3}s",
         alg,
         elems,
         ops_per_elem,
         cs_per_elem,
         cs_per_bit,
+        chain.depth(),
+        build_time.as_secs_f64(),
+    );
+    let lower = bounds::lower_bound(&target);
+    let counting = bounds::counting_bound(elems, <F as PrimeField>::size_in_bits(), 10);
+    println!(
+        "Lower bound: {:>8}, ratio: {:>6.2}; counting bound: {:>8}, ratio: {:>6.2}",
+        lower,
+        adds as f64 / lower as f64,
+        counting,
+        adds as f64 / counting as f64,
     );
+    println!(
+        "Optimize saves {:>8} adds",
+        adds - optimize::optimize(&chain).adds.len()
+    );
+    if elems == 1 {
+        let bits = target[0].into_repr().to_bits_le();
+        let binary =
+            bits.iter().rposition(|b| *b).unwrap_or(0) + bits.iter().filter(|b| **b).count();
+        println!(
+            "Scalar chain saves {:>8} adds over double-and-add",
+            binary as isize - 1 - adds as isize,
+        );
+    }
+    if let Some(step) = opt.fixed_base {
+        let table = fixed_base::Table::every(step, <F as PrimeField>::size_in_bits());
+        let fixed_adds = fixed_base::build_chain(target.clone(), &table, &builder)
+            .adds
+            .len();
+        println!(
+            "Fixed-base table ({} entries per base) saves {:>8.2} adds per elem",
+            table.len(),
+            (adds as f64 - fixed_adds as f64) / elems as f64,
+        );
+    }
+    if let Some(halvings) = opt.chunks {
+        for chunk in (0..=halvings).map(|h| elems >> h).filter(|c| *c > 0) {
+            let start = Instant::now();
+            #[cfg(feature = "parallel")]
+            let chunked = chunked::build_chain_par(target.clone(), chunk, &builder);
+            #[cfg(not(feature = "parallel"))]
+            let chunked = chunked::build_chain(target.clone(), chunk, &builder);
+            println!(
+                "Chunk: {:>8}, Adds per elem: {:>8.1}, Time: {:>8.3}s",
+                chunk,
+                chunked.adds.len() as f64 / elems as f64,
+                start.elapsed().as_secs_f64(),
+            );
+        }
+    }
+    if opt.glv {
+        let glv_adds = glv.build_chain(target.clone(), &builder).chain.adds.len();
+        println!(
+            "GLV saves {:>8.2} adds per elem",
+            (adds as f64 - glv_adds as f64) / elems as f64,
+        );
+    }
+    if *alg == Alg::Signed {
+        let unsigned_adds = bos_coster_fast::build_chain(target).adds.len();
+        println!(
+            "Signed saves {:>8.2} ops per elem over Fast",
+            (unsigned_adds as f64 - adds as f64) / elems as f64,
+        );
+    }
+}
+
+/// Time the ManyPar builder in thread pools of increasing size, against the sequential Fast builder.
+#[cfg(feature = "parallel")]
+fn time_threads<F: PrimeField>(target: Vec<F>, max_threads: usize) {
+    let elems = target.len();
+    let start = Instant::now();
+    let fast_adds = bos_coster_fast::build_chain(target.clone()).adds.len();
+    let fast = start.elapsed();
+    println!(
+        "Fast,         elems: {:>8}, Adds per elem: {:>8.1}, Time: {:>8.3}s",
+        elems,
+        fast_adds as f64 / elems as f64,
+        fast.as_secs_f64(),
+    );
+    let threads = std::iter::successors(Some(1), |t| Some(t * 2)).take_while(|t| *t <= max_threads);
+    for threads in threads {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .expect("cannot build the thread pool");
+        let start = Instant::now();
+        let adds = pool
+            .install(|| vector_addition_chain::bos_coster_par::build_chain(target.clone()))
+            .adds
+            .len();
+        let time = start.elapsed();
+        println!(
+            "Threads: {:>4}, elems: {:>8}, Adds per elem: {:>8.1}, Time: {:>8.3}s, speedup over Fast: {:>6.2}",
+            threads,
+            elems,
+            adds as f64 / elems as f64,
+            time.as_secs_f64(),
+            fast.as_secs_f64() / time.as_secs_f64(),
+        );
+    }
+}
+
+#[cfg(not(feature = "parallel"))]
+fn time_threads<F: PrimeField>(_target: Vec<F>, _max_threads: usize) {
+    panic!("--threads needs the `parallel` feature");
+}
+
+/// The number of additions before the final scalar multiplication in the Fast builder, computed
+/// with std's BinaryHeap.
+fn binary_heap_adds<S: Scalar>(target: Vec<S>) -> usize {
+    let mut heap: BinaryHeap<(S, usize)> = target
+        .into_iter()
+        .enumerate()
+        .filter(|(_, x)| !x.is_zero())
+        .map(|(g, x)| (x, g))
+        .collect();
+    let mut adds = 0;
+    while heap.len() > 1 {
+        let (mut x1, g1) = heap.pop().unwrap();
+        let (x2, g2) = heap.pop().unwrap();
+        let mut half = x1.clone();
+        half.div2();
+        // the label of each addition is irrelevant here, beyond being new
+        let label = usize::MAX - adds;
+        adds += 1;
+        if half > x2 {
+            if x1.is_odd() {
+                heap.push((S::one(), g1));
+            }
+            heap.push((x2, g2));
+            heap.push((half, label));
+        } else {
+            assert!(!x1.sub_noborrow(&x2));
+            heap.push((x2, label));
+            if !x1.is_zero() {
+                heap.push((x1, g1));
+            }
+        }
+    }
+    adds
 }
 
 fn main() {
     let opt = Opt::from_args();
     let rng = &mut rand::thread_rng();
     //let rng = &mut ark_std::test_rng();
-    test::<Fr, _>(opt.size, opt.alg, rng);
+    // z^2 - 1 for the BLS parameter z
+    let lambda = Fr::from(0xd201000000010000u64).square() - Fr::one();
+    test::<Fr, _>(&opt, rng, &glv::Glv::new(lambda));
     //check_chain(&chain, &target);
 }
@@ -1,7 +1,9 @@
-use super::VecAddChain;
-use ark_ff::{BigInteger, PrimeField};
+use super::heap::{Keyed, RadixHeap};
+use super::scalar::{self, ArkBigInt, Scalar};
+use super::{scalar_chain, VecAddChain};
+use ark_ff::PrimeField;
 use std::cmp::{Ord, PartialOrd};
-use std::collections::BinaryHeap;
+use std::collections::VecDeque;
 
 #[derive(Eq, PartialEq, Ord, PartialOrd)]
 pub struct Entry<B> {
@@ -9,89 +11,145 @@ pub struct Entry<B> {
     pub g: usize,
 }
 
-struct State<B> {
-    chain: VecAddChain,
-    heap: BinaryHeap<Entry<B>>,
+impl<B: Scalar> Keyed for Entry<B> {
+    fn key(&self) -> u64 {
+        self.x.prefix()
+    }
 }
 
-impl<F: BigInteger> State<F> {
-    fn add(&mut self, a: usize, b: usize) -> usize {
-        let (a, b) = if a < b { (a, b) } else { (b, a) };
-        self.chain.adds.push((a, b));
-        self.chain.adds.len() + self.chain.dimension - 1
-    }
-    fn new(target: Vec<F>) -> Self {
-        let mut this = State {
-            chain: VecAddChain {
-                adds: vec![],
-                dimension: target.len(),
-            },
-            heap: BinaryHeap::new(),
+/// The additions of [build_chain_scalars], as `(a, b, sub)` triples for [VecAddChain::push],
+/// produced one at a time.
+///
+/// Only the heap is kept, so memory is bounded by the dimension, not by the length of the chain.
+/// See [crate::stream] for writing the additions out as they come.
+pub struct Steps<B> {
+    dimension: usize,
+    next_label: usize,
+    heap: RadixHeap<Entry<B>>,
+    pending: VecDeque<(usize, usize, bool)>,
+}
+
+impl<F: Scalar> Steps<F> {
+    /// Panics if `target` is zero.
+    pub fn new(target: Vec<F>) -> Self {
+        let mut this = Steps {
+            dimension: target.len(),
+            next_label: target.len(),
+            heap: RadixHeap::new(),
+            pending: VecDeque::new(),
         };
         for (i, f) in target.into_iter().enumerate() {
             if !f.is_zero() {
                 this.heap.push(Entry { x: f, g: i })
             }
         }
+        assert!(this.heap.len() > 0, "zero target");
         this
     }
-    fn finalize(mut self) -> VecAddChain {
-        assert_eq!(self.heap.len(), 1);
-        let entry = self.heap.pop().unwrap();
-        let mut p2 = entry.g;
-        let mut acc = None;
-        let mut scalar = entry.x;
-        while !scalar.is_zero() {
-            if scalar.is_odd() {
-                acc = Some(match acc {
-                    Some(acc) => self.add(acc, p2),
-                    None => p2,
-                });
-            }
-            scalar.div2();
-            p2 = self.add(p2, p2);
-        }
-        // safe b/c non-zero things are eventually odd when divided by 2
-        let acc = acc.unwrap();
-        if acc < self.chain.dimension {
-            self.chain.adds.clear();
-        } else {
-            self.chain.adds.truncate(acc - self.chain.dimension + 1);
-        }
-        self.chain
+    pub fn dimension(&self) -> usize {
+        self.dimension
     }
-}
-
-pub fn build_chain<F: PrimeField>(target: Vec<F>) -> VecAddChain {
-    let mut state = State::<F::BigInt>::new(target.into_iter().map(|f| f.into_repr()).collect());
-    while state.heap.len() > 1 {
-        let mut first = state.heap.pop().unwrap();
-        let mut second = state.heap.pop().unwrap();
+    /// The number of nonzero scalars left. Each step before one is left reduces them; the steps
+    /// after that multiply the last one by its scalar.
+    pub fn remaining(&self) -> usize {
+        self.heap.len()
+    }
+    fn push(&mut self, a: usize, b: usize, sub: bool) -> usize {
+        self.pending.push_back((a, b, sub));
+        self.next_label += 1;
+        self.next_label - 1
+    }
+    fn add(&mut self, a: usize, b: usize) -> usize {
+        let (a, b) = if a < b { (a, b) } else { (b, a) };
+        self.push(a, b, false)
+    }
+    fn step(&mut self) {
+        self.heap.fill();
+        let s = self.heap.second();
+        let (first, second) = (self.heap.get(0), self.heap.get(s));
         let half_first = {
-            let mut t = first.x;
+            let mut t = first.x.clone();
             t.div2();
             t
         };
         if half_first > second.x {
-            state.heap.push(second);
-            if first.x.is_odd() {
-                state.heap.push(Entry {
-                    x: F::one().into_repr(),
-                    g: first.g.clone(),
-                });
+            let (g, odd) = (first.g, first.x.is_odd());
+            let double = self.add(g, g);
+            self.heap.replace(
+                0,
+                Entry {
+                    x: half_first,
+                    g: double,
+                },
+            );
+            if odd {
+                self.heap.push(Entry { x: F::one(), g });
             }
-            first.x.div2();
-            first.g = state.add(first.g.clone(), first.g);
-            state.heap.push(first);
         } else {
-            assert!(!first.x.sub_noborrow(&second.x));
-            //first.x -= second.x;
-            second.g = state.add(first.g.clone(), second.g);
-            state.heap.push(second);
-            if !first.x.is_zero() {
-                state.heap.push(first);
+            let (g, x) = (second.g, second.x.clone());
+            let sum = self.add(first.g, g);
+            // the second entry, with its new term, becomes the largest
+            let mut first = self.heap.replace(0, Entry { x, g: sum });
+            assert!(!first.x.sub_noborrow(&self.heap.get(s).x));
+            if first.x.is_zero() {
+                self.heap.remove(s);
+            } else {
+                self.heap.replace(s, first);
+            }
+        }
+    }
+    fn finalize(&mut self) {
+        self.heap.fill();
+        let (bits, g) = {
+            let entry = self.heap.get(0);
+            (scalar::to_bits_le(&entry.x), entry.g)
+        };
+        self.heap.remove(0);
+        let steps = scalar_chain::best_chain(&bits);
+        let acc = scalar_chain::apply(&steps, g, |a, b| self.add(*a, *b));
+        // as in VecAddChain::set_output; the last entry always holds the last term otherwise
+        if acc + 1 < self.dimension {
+            let double = self.push(acc, acc, false);
+            self.push(double, acc, true);
+        }
+    }
+}
+
+impl<F: Scalar> Iterator for Steps<F> {
+    type Item = (usize, usize, bool);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(step) = self.pending.pop_front() {
+                return Some(step);
+            }
+            match self.heap.len() {
+                0 => return None,
+                1 => self.finalize(),
+                _ => self.step(),
             }
         }
     }
-    state.finalize()
+}
+
+pub fn build_chain<F: PrimeField>(target: Vec<F>) -> VecAddChain {
+    build_chain_scalars(
+        target
+            .into_iter()
+            .map(|f| ArkBigInt(f.into_repr()))
+            .collect(),
+    )
+}
+
+/// [build_chain], for any [Scalar]s.
+pub fn build_chain_scalars<S: Scalar>(target: Vec<S>) -> VecAddChain {
+    let steps = Steps::new(target);
+    let mut chain = VecAddChain {
+        adds: vec![],
+        subs: vec![],
+        dimension: steps.dimension(),
+    };
+    for (a, b, sub) in steps {
+        chain.push(a, b, sub);
+    }
+    chain
 }
@@ -0,0 +1,156 @@
+//! A multithreaded Bos-Coster builder (with the `parallel` feature).
+//!
+//! It makes the same additions as [crate::bos_coster_fast], grouped so that most of them can be
+//! made concurrently. Entries are kept in buckets by bit length (the top bits of
+//! [Scalar::prefix]). When the top bucket has entries `x_1 >= x_2 >= ... >= x_k`, the sequential
+//! builder subtracts each from the one before: `x_1 - x_2` drops to a lower bucket and `x_2` gets
+//! the new term, which is then the largest, and so on down to `x_k`. Every one of those additions
+//! has its operands and label known up front, so the bucket is sorted, subtracted and re-bucketed
+//! in parallel. A top bucket with a single entry is halved or subtracted from the largest entry
+//! below it, one addition at a time, as in the sequential builder.
+use super::bos_coster_fast::Entry;
+use super::scalar::{self, ArkBigInt, Scalar};
+use super::{scalar_chain, VecAddChain};
+use ark_ff::PrimeField;
+use rayon::prelude::*;
+
+/// The bucket of an entry: the bit length of its scalar, for the scalars this crate implements.
+fn bucket<S: Scalar>(x: &S) -> usize {
+    (x.prefix() >> 55) as usize
+}
+
+pub fn build_chain<F: PrimeField>(target: Vec<F>) -> VecAddChain {
+    build_chain_scalars(
+        target
+            .into_par_iter()
+            .map(|f| ArkBigInt(f.into_repr()))
+            .collect(),
+    )
+}
+
+/// [build_chain], for any [Scalar]s.
+pub fn build_chain_scalars<S: Scalar + Send + Sync>(target: Vec<S>) -> VecAddChain {
+    let mut chain = VecAddChain {
+        dimension: target.len(),
+        adds: vec![],
+        subs: vec![],
+    };
+    let mut buckets: Vec<Vec<Entry<S>>> = Vec::new();
+    let entries = target
+        .into_par_iter()
+        .enumerate()
+        .map(|(g, x)| Entry { x, g })
+        .collect();
+    fill(&mut buckets, entries);
+    let mut round = Vec::new();
+    let last = loop {
+        let top = buckets.iter().rposition(|b| !b.is_empty()).expect("zero target");
+        std::mem::swap(&mut round, &mut buckets[top]);
+        if round.len() == 1 {
+            let top = round.pop().unwrap();
+            match buckets[..bucket(&top.x)].iter().rposition(|b| !b.is_empty()) {
+                Some(next) => step(&mut chain, &mut buckets, next, top),
+                None => break top,
+            }
+            continue;
+        }
+        // x_i - x_{i+1} keeps x_i's term, and x_{i+1} gets term i of the round
+        round.par_sort_unstable_by(|a, b| b.cmp(a));
+        let base = chain.dimension + chain.adds.len();
+        let first = round[0].g;
+        let term = |i: usize| if i == 0 { first } else { base + i - 1 };
+        let mut out = (std::mem::take(&mut chain.adds), Vec::new());
+        out.par_extend(round.par_windows(2).enumerate().map(|(i, pair)| {
+            let (g, h) = (term(i), pair[1].g);
+            let mut x = pair[0].x.clone();
+            assert!(!x.sub_noborrow(&pair[1].x));
+            ((g.min(h), g.max(h)), Entry { x, g })
+        }));
+        let (adds, mut diffs) = out;
+        chain.adds = adds;
+        let mut kept = round.pop().unwrap();
+        kept.g = term(round.len());
+        round.clear();
+        diffs.push(kept);
+        fill(&mut buckets, diffs);
+    };
+    let steps = scalar_chain::best_chain(&scalar::to_bits_le(&last.x));
+    let acc = scalar_chain::apply(&steps, last.g, |a, b| chain.push(*a, *b, false));
+    chain.set_output(acc);
+    chain
+}
+
+/// Put `entry` in its bucket.
+fn insert<S: Scalar>(buckets: &mut Vec<Vec<Entry<S>>>, entry: Entry<S>) {
+    let b = bucket(&entry.x);
+    if buckets.len() <= b {
+        buckets.resize_with(b + 1, Vec::new);
+    }
+    buckets[b].push(entry);
+}
+
+/// Put the nonzero `entries` in their buckets: each thread buckets its share, and then the shares
+/// are appended.
+fn fill<S: Scalar + Send + Sync>(buckets: &mut Vec<Vec<Entry<S>>>, entries: Vec<Entry<S>>) {
+    let shares: Vec<Vec<Vec<Entry<S>>>> = entries
+        .into_par_iter()
+        .filter(|e| !e.x.is_zero())
+        .fold(Vec::new, |mut share, e| {
+            insert(&mut share, e);
+            share
+        })
+        .collect();
+    for share in shares {
+        if buckets.len() < share.len() {
+            buckets.resize_with(share.len(), Vec::new);
+        }
+        for (bucket, mut entries) in buckets.iter_mut().zip(share) {
+            // copy the smaller of the two
+            if bucket.len() < entries.len() {
+                std::mem::swap(bucket, &mut entries);
+            }
+            bucket.append(&mut entries);
+        }
+    }
+}
+
+/// Reduce `top`, the only entry above bucket `next`, against the largest entry of `next`: halve
+/// it while that leaves it the largest, then subtract.
+fn step<S: Scalar + Send + Sync>(
+    chain: &mut VecAddChain,
+    buckets: &mut Vec<Vec<Entry<S>>>,
+    next: usize,
+    mut top: Entry<S>,
+) {
+    let (s, second) = buckets[next]
+        .par_iter()
+        .enumerate()
+        .max_by(|a, b| a.1.cmp(b.1))
+        .map(|(s, e)| (s, e.x.clone()))
+        .unwrap();
+    loop {
+        let mut half = top.x.clone();
+        half.div2();
+        if half > second {
+            if top.x.is_odd() {
+                insert(
+                    buckets,
+                    Entry {
+                        x: S::one(),
+                        g: top.g,
+                    },
+                );
+            }
+            top = Entry {
+                x: half,
+                g: chain.push(top.g, top.g, false),
+            };
+        } else {
+            let g = &mut buckets[next][s].g;
+            *g = chain.push(top.g.min(*g), top.g.max(*g), false);
+            assert!(!top.x.sub_noborrow(&second));
+            break;
+        }
+    }
+    insert(buckets, top);
+}
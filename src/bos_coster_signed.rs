@@ -0,0 +1,172 @@
+//! Bos-Coster with subtractions.
+//!
+//! Like [bos_coster_fast](super::bos_coster_fast), but each heap entry's term may be negated,
+//! which lets the builder round odd scalars up as well as down when halving (leaving remainders
+//! of +-1, as in NAF), and multiply out the final entry in NAF. Negating a term is free: it is
+//! folded into a later subtraction.
+use super::scalar::{ArkBigInt, Scalar};
+use super::VecAddChain;
+use ark_ff::PrimeField;
+use std::cmp::{Ord, PartialOrd};
+use std::collections::BinaryHeap;
+
+#[derive(Eq, PartialEq, Ord, PartialOrd)]
+pub struct Entry<B> {
+    pub x: B,
+    pub g: usize,
+    /// Whether the term is `-g`, rather than `g`.
+    pub neg: bool,
+}
+
+struct State<B> {
+    chain: VecAddChain,
+    heap: BinaryHeap<Entry<B>>,
+}
+
+/// Whether bit 1 of `x` is set.
+fn bit1<S: Scalar>(x: &S) -> bool {
+    let mut half = x.clone();
+    half.div2();
+    half.is_odd()
+}
+
+/// The NAF digits of `x`, least significant first.
+fn naf<S: Scalar>(mut x: S) -> Vec<i8> {
+    let mut digits = Vec::new();
+    while !x.is_zero() {
+        // for odd x, round to a multiple of 4 (halving rounds down, so rounding up adds one after)
+        let d = if !x.is_odd() {
+            0
+        } else if bit1(&x) {
+            -1
+        } else {
+            1
+        };
+        x.div2();
+        if d < 0 {
+            assert!(!x.add_nocarry(&S::one()));
+        }
+        digits.push(d);
+    }
+    digits
+}
+
+impl<S: Scalar> State<S> {
+    /// Add two signed terms.
+    fn add(&mut self, (a, a_neg): (usize, bool), (b, b_neg): (usize, bool)) -> (usize, bool) {
+        match (a_neg, b_neg) {
+            (false, true) => (self.chain.push(a, b, true), false),
+            (true, false) => (self.chain.push(b, a, true), false),
+            _ => {
+                let (a, b) = if a < b { (a, b) } else { (b, a) };
+                (self.chain.push(a, b, false), a_neg)
+            }
+        }
+    }
+    fn new(target: Vec<S>) -> Self {
+        let mut this = State {
+            chain: VecAddChain {
+                adds: vec![],
+                subs: vec![],
+                dimension: target.len(),
+            },
+            heap: BinaryHeap::new(),
+        };
+        for (i, f) in target.into_iter().enumerate() {
+            if !f.is_zero() {
+                this.heap.push(Entry {
+                    x: f,
+                    g: i,
+                    neg: false,
+                })
+            }
+        }
+        this
+    }
+    fn finalize(mut self) -> VecAddChain {
+        assert_eq!(self.heap.len(), 1);
+        let entry = self.heap.pop().unwrap();
+        let mut g = (entry.g, entry.neg);
+        if g.1 {
+            // -g = g - 2g
+            let double = self.add(g, g);
+            g = (self.chain.push(g.0, double.0, true), false);
+        }
+        let mut digits = naf(entry.x).into_iter().rev().skip_while(|d| *d == 0);
+        assert_eq!(digits.next(), Some(1));
+        let mut acc = g;
+        for d in digits {
+            acc = self.add(acc, acc);
+            if d != 0 {
+                acc = self.add(acc, (g.0, d < 0));
+            }
+        }
+        assert!(!acc.1);
+        self.chain.set_output(acc.0);
+        self.chain
+    }
+}
+
+pub fn build_chain<F: PrimeField>(target: Vec<F>) -> VecAddChain {
+    build_chain_scalars(
+        target
+            .into_iter()
+            .map(|f| ArkBigInt(f.into_repr()))
+            .collect(),
+    )
+}
+
+/// [build_chain], for any [Scalar]s.
+pub fn build_chain_scalars<S: Scalar>(target: Vec<S>) -> VecAddChain {
+    let mut state = State::new(target);
+    let one = S::one();
+    let mut three = S::one();
+    three.add_nocarry(&one);
+    three.add_nocarry(&one);
+    while state.heap.len() > 1 {
+        let mut first = state.heap.pop().unwrap();
+        let mut second = state.heap.pop().unwrap();
+        let half_first = {
+            let mut t = first.x.clone();
+            t.div2();
+            t
+        };
+        if half_first > second.x {
+            state.heap.push(second);
+            if first.x.is_odd() {
+                // round to a multiple of 4, leaving a remainder of +-1 (but 3 = 2 + 1 is cheaper)
+                let mut up = first.x.clone();
+                if bit1(&first.x) && first.x != three && !up.add_nocarry(&one) {
+                    state.heap.push(Entry {
+                        x: one.clone(),
+                        g: first.g,
+                        neg: !first.neg,
+                    });
+                    first.x = up;
+                } else {
+                    state.heap.push(Entry {
+                        x: one.clone(),
+                        g: first.g,
+                        neg: first.neg,
+                    });
+                }
+            }
+            first.x.div2();
+            let (g, neg) = state.add((first.g, first.neg), (first.g, first.neg));
+            first.g = g;
+            first.neg = neg;
+            state.heap.push(first);
+        } else {
+            assert!(!first.x.sub_noborrow(&second.x));
+            //first.x -= second.x;
+            let (g, neg) = state.add((first.g, first.neg), (second.g, second.neg));
+            second.g = g;
+            second.neg = neg;
+            state.heap.push(second);
+            if !first.x.is_zero() {
+                state.heap.push(first);
+            }
+        }
+    }
+    state.finalize()
+}
@@ -0,0 +1,46 @@
+//! Lower bounds on the length of vector addition chains, to measure builders against.
+//!
+//! [lower_bound] holds for every chain (with or without subtractions) for a given target.
+//! [counting_bound] holds for all but a tiny fraction of targets, so it is the better yardstick for
+//! random targets.
+use ark_ff::{BigInteger, PrimeField};
+
+/// A lower bound on the number of additions in any chain for `target`.
+///
+/// Let `k` be the largest coordinate, taking each coordinate as `c` or `-c`, whichever is
+/// smaller, and `m` the number of nonzero coordinates. Then any chain needs `ceil(log2 k)`
+/// additions that grow `k`'s coordinate (each at most doubling it), plus `m - 1` more to bring in the
+/// other bases, which can't grow it: at least `ceil(log2 k) + m - 1` in all.
+pub fn lower_bound<F: PrimeField>(target: &[F]) -> usize {
+    let nonzero: Vec<&F> = target.iter().filter(|f| !f.is_zero()).collect();
+    let largest = nonzero
+        .iter()
+        .map(|f| f.into_repr().min((-**f).into_repr()))
+        .max();
+    match largest {
+        // ceil(log2 k) is the bit length of k - 1
+        Some(mut k) => {
+            k.sub_noborrow(&F::BigInt::from(1));
+            k.num_bits() as usize + nonzero.len() - 1
+        }
+        None => 0,
+    }
+}
+
+/// A number of additions that all but a `2^-slack` fraction of targets of `dimension` scalars of
+/// `bits` bits need.
+///
+/// There are fewer than `2 (dimension + i)^2` choices for step `i` (two operands, and whether to
+/// subtract), so few chains are short, and short chains compute few targets.
+pub fn counting_bound(dimension: usize, bits: usize, slack: usize) -> usize {
+    // at least 2^targets targets (or just the zero target, for no bits)
+    let targets = (dimension * bits.saturating_sub(1)) as f64 - slack as f64;
+    // log2 of (twice) the number of chains of length below `s`
+    let mut chains = 1f64;
+    let mut s = 0;
+    while chains <= targets {
+        chains += 1.0 + 2.0 * ((dimension + s) as f64).log2();
+        s += 1;
+    }
+    s
+}
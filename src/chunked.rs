@@ -0,0 +1,89 @@
+//! Building chains for large targets in chunks.
+//!
+//! [build_chain] splits the target into chunks of a given size, builds a chain for each with any
+//! builder, and merges them: the chunk chains are relabelled and concatenated, and a final short
+//! chain sums their outputs. Smaller chunks build faster (and, with the `parallel` feature,
+//! concurrently, see [build_chain_par]), but share less work, so the chain is longer.
+use super::VecAddChain;
+use ark_ff::PrimeField;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Build a chain for `target` from chains for its chunks of `chunk` entries, built by `builder`.
+///
+/// Panics if `target` is zero.
+pub fn build_chain<F: PrimeField>(
+    target: Vec<F>,
+    chunk: usize,
+    builder: impl Fn(Vec<F>) -> VecAddChain,
+) -> VecAddChain {
+    assert!(chunk > 0);
+    let chains = target
+        .chunks(chunk)
+        .map(|c| build_chunk(c, &builder))
+        .collect();
+    merge(target.len(), chunk, chains)
+}
+
+/// [build_chain], building the chunks concurrently.
+#[cfg(feature = "parallel")]
+pub fn build_chain_par<F: PrimeField>(
+    target: Vec<F>,
+    chunk: usize,
+    builder: impl Fn(Vec<F>) -> VecAddChain + Sync,
+) -> VecAddChain {
+    assert!(chunk > 0);
+    let chains = target
+        .par_chunks(chunk)
+        .map(|c| build_chunk(c, &builder))
+        .collect();
+    merge(target.len(), chunk, chains)
+}
+
+/// The chain for `chunk`, or `None` if it is zero.
+fn build_chunk<F: PrimeField>(
+    chunk: &[F],
+    builder: &impl Fn(Vec<F>) -> VecAddChain,
+) -> Option<VecAddChain> {
+    if chunk.iter().all(|f| f.is_zero()) {
+        None
+    } else {
+        Some(builder(chunk.to_vec()))
+    }
+}
+
+/// Concatenate the chains for consecutive chunks of `chunk` entries, and sum their outputs.
+fn merge(dimension: usize, chunk: usize, chains: Vec<Option<VecAddChain>>) -> VecAddChain {
+    let mut out = VecAddChain {
+        dimension,
+        adds: Vec::with_capacity(chains.iter().flatten().map(|c| c.adds.len() + 1).sum()),
+        subs: vec![],
+    };
+    let mut acc = None;
+    for (i, chain) in chains.into_iter().enumerate() {
+        let chain = match chain {
+            Some(chain) => chain,
+            None => continue,
+        };
+        let offset = i * chunk;
+        let base = out.dimension + out.adds.len();
+        let relabel = |l: usize| {
+            if l < chain.dimension {
+                offset + l
+            } else {
+                base + l - chain.dimension
+            }
+        };
+        for (step, (a, b)) in chain.adds.iter().enumerate() {
+            out.push(relabel(*a), relabel(*b), chain.is_sub(step));
+        }
+        // a chain's output is its last term
+        let output = relabel(chain.dimension + chain.adds.len() - 1);
+        acc = Some(match acc {
+            Some(acc) => out.push(acc, output, false),
+            None => output,
+        });
+    }
+    out.set_output(acc.expect("zero target"));
+    out
+}
@@ -0,0 +1,123 @@
+//! Chains that are safe for incomplete addition formulas.
+//!
+//! [make_complete] rewrites a chain so that no addition of distinct labels has operands that are
+//! equal or opposite as symbolic vectors: equal operands become an explicit doubling `(a, a)`, and
+//! opposite operands (which sum to zero) are dropped, along with the additions of that zero.
+//!
+//! In the result, only the doubling steps need a complete (or doubling) formula.
+use super::sparse::{self, SparseVec};
+use super::VecAddChain;
+use ark_ff::{Field, PrimeField};
+
+/// A chain with no symbolically exceptional additions, other than explicit doublings.
+#[derive(Debug)]
+pub struct CompleteChain {
+    pub chain: VecAddChain,
+    /// The number of additions of equal terms that became explicit doublings.
+    pub new_doublings: usize,
+    /// The number of additions removed because an operand was zero, or they summed to zero.
+    pub removed: usize,
+    /// The number of additions in the chain before the rewrite.
+    pub plain_adds: usize,
+}
+
+impl CompleteChain {
+    /// The indices of the additions that are doublings.
+    pub fn doubling_steps(&self) -> Vec<usize> {
+        self.chain
+            .adds
+            .iter()
+            .enumerate()
+            .filter(|(_, (a, b))| a == b)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// The additions this chain costs over the plain one; negative if removing additions saved
+    /// more than negating the output cost.
+    pub fn extra_adds(&self) -> isize {
+        self.chain.adds.len() as isize - self.plain_adds as isize
+    }
+}
+
+/// Rewrite `chain` (over a group of order `|F|`) so that it has no exceptional additions.
+///
+/// Panics if the chain is malformed, or computes zero.
+pub fn make_complete<F: Field>(chain: &VecAddChain) -> CompleteChain {
+    let dimension = chain.dimension;
+    // For each old label, `None` if its term is zero, or `Some((l, neg))` if its term is that of
+    // new label `l`, negated if `neg`.
+    let mut labels: Vec<Option<(usize, bool)>> = (0..dimension).map(|i| Some((i, false))).collect();
+    let mut out = VecAddChain {
+        dimension,
+        adds: Vec::new(),
+        subs: Vec::new(),
+    };
+    let mut new_doublings = 0;
+    let mut removed = 0;
+    let bases: Vec<SparseVec<F>> = (0..dimension).map(sparse::basis).collect();
+    chain
+        .sparse_fold::<F, _>(bases, |step, x, y, _| {
+            let (a, b) = chain.adds[step];
+            let sub = chain.is_sub(step);
+            // the step adds `x` and `±y`
+            let (equal, opposite) = if sub {
+                (sparse::is_opposite(x, y), x == y)
+            } else {
+                (x == y, sparse::is_opposite(x, y))
+            };
+            let label = match (labels[a], labels[b]) {
+                (la, None) => {
+                    removed += 1;
+                    la
+                }
+                (None, lb) => {
+                    removed += 1;
+                    lb.map(|(l, neg)| (l, neg ^ sub))
+                }
+                _ if opposite => {
+                    removed += 1;
+                    None
+                }
+                (Some((la, na)), Some(_)) if equal => {
+                    new_doublings += (a != b) as usize;
+                    Some((out.push(la, la, false), na))
+                }
+                (Some((la, na)), Some((lb, nb))) => Some(match (na, nb ^ sub) {
+                    (false, false) => (out.push(la, lb, false), false),
+                    (true, true) => (out.push(la, lb, false), true),
+                    (false, true) => (out.push(la, lb, true), false),
+                    (true, false) => (out.push(lb, la, true), false),
+                }),
+            };
+            labels.push(label);
+            Ok(())
+        })
+        .expect("malformed chain");
+    let (output, neg) = labels
+        .last()
+        .copied()
+        .flatten()
+        .expect("chain computes zero");
+    out.set_output(output);
+    if neg {
+        // -t = t - 2t
+        let output = out.dimension + out.adds.len() - 1;
+        let double = out.push(output, output, false);
+        out.push(output, double, true);
+    }
+    CompleteChain {
+        chain: out,
+        new_doublings,
+        removed,
+        plain_adds: chain.adds.len(),
+    }
+}
+
+/// Build a chain for `target` with `builder`, then make it complete.
+pub fn build_chain<F: PrimeField>(
+    target: Vec<F>,
+    builder: impl FnOnce(Vec<F>) -> VecAddChain,
+) -> CompleteChain {
+    make_complete::<F>(&builder(target))
+}
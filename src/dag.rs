@@ -0,0 +1,186 @@
+//! Deduplicated term DAGs, for the Bos-Coster builders.
+//!
+//! A [Dag] makes each distinct [Term] once: adding the same two terms again returns the existing
+//! sum. Terms are numbered in the order they are made, and ordered by number.
+//!
+//! By default terms are hash-consed with the `hashconsing` crate, as [ChainData]. Without the
+//! `hashconsing` feature they are indices into an arena instead, which needs no care when dropped;
+//! the chains built are the same.
+use super::VecAddChain;
+use std::cmp::max;
+
+#[derive(Hash, PartialEq, Eq, Clone, PartialOrd, Ord)]
+pub enum Form {
+    Add(Term, Term),
+    Basis(usize),
+}
+
+#[cfg(feature = "hashconsing")]
+mod imp {
+    use super::Form;
+    use hashconsing::{HConsed, HConsign, HashConsign};
+
+    #[derive(Hash, PartialEq, Eq, Clone, PartialOrd, Ord)]
+    pub struct ChainData {
+        pub depth: usize,
+        pub form: Form,
+    }
+
+    pub type Term = HConsed<ChainData>;
+
+    pub(super) fn id(t: &Term) -> usize {
+        t.uid() as usize
+    }
+
+    pub struct Dag {
+        terms: HConsign<ChainData>,
+        /// All terms, smallest first. Dropped largest first, so no drop recurses.
+        drop_list: Vec<Term>,
+    }
+
+    impl Dag {
+        pub fn new() -> Self {
+            Dag {
+                terms: HConsign::empty(),
+                drop_list: Vec::new(),
+            }
+        }
+        pub fn len(&self) -> usize {
+            self.drop_list.len()
+        }
+        pub fn form<'a>(&'a self, t: &'a Term) -> &'a Form {
+            &t.form
+        }
+        pub(super) fn make(&mut self, depth: usize, form: Form) -> Term {
+            let (t, new) = self.terms.mk_is_new(ChainData { depth, form });
+            if new {
+                self.drop_list.push(t.clone());
+            }
+            t
+        }
+    }
+
+    impl Drop for Dag {
+        fn drop(&mut self) {
+            // drain table first, to control drop order
+            self.terms.table.drain();
+            while let Some(_t) = self.drop_list.pop() {
+                // Drop most complex first. Children still in list, so no recursive drop.
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "hashconsing"))]
+mod imp {
+    use super::Form;
+    use std::collections::HashMap;
+
+    #[derive(Hash, PartialEq, Eq, Clone, PartialOrd, Ord)]
+    pub struct Term {
+        id: usize,
+        pub depth: usize,
+    }
+
+    pub(super) fn id(t: &Term) -> usize {
+        t.id
+    }
+
+    pub struct Dag {
+        forms: Vec<Form>,
+        terms: HashMap<Form, Term>,
+    }
+
+    impl Dag {
+        pub fn new() -> Self {
+            Dag {
+                forms: Vec::new(),
+                terms: HashMap::new(),
+            }
+        }
+        pub fn len(&self) -> usize {
+            self.forms.len()
+        }
+        pub fn form<'a>(&'a self, t: &'a Term) -> &'a Form {
+            &self.forms[t.id]
+        }
+        pub(super) fn make(&mut self, depth: usize, form: Form) -> Term {
+            let id = self.forms.len();
+            let forms = &mut self.forms;
+            self.terms
+                .entry(form)
+                .or_insert_with_key(|form| {
+                    forms.push(form.clone());
+                    Term { id, depth }
+                })
+                .clone()
+        }
+    }
+}
+
+#[cfg(feature = "hashconsing")]
+pub use imp::ChainData;
+use imp::id;
+pub use imp::{Dag, Term};
+
+impl Default for Dag {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Dag {
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The `index`th basis vector.
+    pub fn basis(&mut self, index: usize) -> Term {
+        self.make(1, Form::Basis(index))
+    }
+
+    pub fn add(&mut self, a: Term, b: Term) -> Term {
+        let (a, b) = if a < b { (a, b) } else { (b, a) };
+        let depth = 1 + max(a.depth, b.depth);
+        self.make(depth, Form::Add(a, b))
+    }
+
+    /// A chain of the given dimension with result `output`, computing the terms it depends on in
+    /// depth-first order. See [VecAddChain::set_output] for when `output` is a basis vector.
+    pub fn chain(&self, dimension: usize, output: Term) -> VecAddChain {
+        const NONE: usize = usize::MAX;
+        let output_id = id(&output);
+        let mut labels = vec![NONE; self.len()];
+        let mut children_added = vec![false; self.len()];
+        let mut stack = vec![output];
+        let mut adds = Vec::new();
+        while let Some(term) = stack.pop() {
+            if labels[id(&term)] != NONE {
+                // reached again through another parent
+                continue;
+            }
+            match self.form(&term) {
+                Form::Add(l, r) => {
+                    if children_added[id(&term)] {
+                        adds.push((labels[id(l)], labels[id(r)]));
+                        labels[id(&term)] = dimension + adds.len() - 1;
+                    } else {
+                        let (l, r) = (l.clone(), r.clone());
+                        children_added[id(&term)] = true;
+                        stack.push(term);
+                        stack.push(l);
+                        stack.push(r);
+                    }
+                }
+                Form::Basis(i) => labels[id(&term)] = *i,
+            }
+        }
+        let mut chain = VecAddChain {
+            dimension,
+            adds,
+            subs: Vec::new(),
+        };
+        chain.set_output(labels[output_id]);
+        chain
+    }
+}
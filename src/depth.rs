@@ -0,0 +1,267 @@
+//! Depth-bounded chains.
+//!
+//! The Bos-Coster builders minimize additions with little regard for depth (see
+//! [VecAddChain::depth]). This module trades additions for depth: [rebalance] reduces depth without
+//! adding steps, [build_windowed] builds chains whose depth and length both follow a digit width,
+//! [build_low_depth] builds chains of depth about `bits + 2 sqrt(bits)`, and [build_chain] searches
+//! the digit width for the shortest chain within a depth bound.
+use super::scalar::{ArkBigInt, Scalar};
+use super::{bos_coster_fast, VecAddChain};
+use ark_ff::{BigInteger, PrimeField};
+use std::cmp::{max, Reverse};
+use std::collections::BinaryHeap;
+
+/// Append `a + b` (or `a - b`) to `chain`, tracking the depth of each new label.
+fn push(chain: &mut VecAddChain, depths: &mut Vec<usize>, a: usize, b: usize, sub: bool) -> usize {
+    depths.push(1 + max(depths[a], depths[b]));
+    chain.push(a, b, sub)
+}
+
+/// Rewrite the chain's sums to be as shallow as possible.
+///
+/// A (non-doubling) step whose term is used exactly once, by another non-doubling step, is merged
+/// into it, which turns the chain into doublings and many-operand sums. Each sum is then rebuilt as
+/// a Huffman tree on its operands' depths. The number of additions does not change.
+///
+/// Panics if the chain is malformed.
+pub fn rebalance(chain: &VecAddChain) -> VecAddChain {
+    chain.validate_structure().expect("malformed chain");
+    let dimension = chain.dimension;
+    let mut uses = vec![0usize; dimension + chain.adds.len()];
+    for (a, b) in &chain.adds {
+        uses[*a] += 1;
+        uses[*b] += 1;
+    }
+    let is_doubling = |label: usize| {
+        let (a, b) = chain.adds[label - dimension];
+        a == b
+    };
+    let mut merged = vec![false; chain.adds.len()];
+    for (a, b) in chain.adds.iter().filter(|(a, b)| a != b) {
+        for &label in &[*a, *b] {
+            if label >= dimension && uses[label] == 1 && !is_doubling(label) {
+                merged[label - dimension] = true;
+            }
+        }
+    }
+
+    let mut out = VecAddChain {
+        dimension,
+        adds: vec![],
+        subs: vec![],
+    };
+    let mut depths = vec![0; dimension];
+    // new label of each old label; merged steps have none
+    let mut labels: Vec<usize> = (0..dimension).collect();
+    for (step, (a, b)) in chain.adds.iter().enumerate() {
+        if merged[step] {
+            labels.push(usize::MAX);
+        } else if a == b {
+            let l = push(&mut out, &mut depths, labels[*a], labels[*a], false);
+            labels.push(l);
+        } else {
+            // the signed operands of the sum, in a min-heap on depth
+            let mut operands = BinaryHeap::new();
+            let mut stack = vec![(*a, false), (*b, chain.is_sub(step))];
+            while let Some((label, neg)) = stack.pop() {
+                if label >= dimension && merged[label - dimension] {
+                    let s = label - dimension;
+                    let (x, y) = chain.adds[s];
+                    stack.push((x, neg));
+                    stack.push((y, neg ^ chain.is_sub(s)));
+                } else {
+                    let l = labels[label];
+                    operands.push(Reverse((depths[l], operands.len(), l, neg)));
+                }
+            }
+            let mut seq = operands.len();
+            while operands.len() > 1 {
+                let Reverse((_, _, x, x_neg)) = operands.pop().unwrap();
+                let Reverse((_, _, y, y_neg)) = operands.pop().unwrap();
+                let (l, neg) = match (x_neg, y_neg) {
+                    (false, false) => (push(&mut out, &mut depths, x, y, false), false),
+                    (false, true) => (push(&mut out, &mut depths, x, y, true), false),
+                    (true, false) => (push(&mut out, &mut depths, y, x, true), false),
+                    (true, true) => (push(&mut out, &mut depths, x, y, false), true),
+                };
+                operands.push(Reverse((depths[l], seq, l, neg)));
+                seq += 1;
+            }
+            let Reverse((_, _, l, neg)) = operands.pop().unwrap();
+            // the first operand of a sum is never negated, so neither is the whole
+            assert!(!neg);
+            labels.push(l);
+        }
+    }
+    out
+}
+
+/// Sum `labels` in a balanced tree, returning `None` if there are none.
+fn balanced_sum(chain: &mut VecAddChain, mut labels: Vec<usize>) -> Option<usize> {
+    while labels.len() > 1 {
+        labels = labels
+            .chunks(2)
+            .map(|pair| match pair {
+                [a, b] => chain.push(*a, *b, false),
+                [a] => *a,
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    labels.pop()
+}
+
+/// The chunk width that minimizes the depth of [build_low_depth], for scalars of `bits` bits.
+pub fn best_chunk(bits: usize) -> usize {
+    (1..=bits.max(1))
+        .min_by_key(|c| c + bits.div_ceil(*c))
+        .unwrap()
+}
+
+/// Build a chain of depth at most about `bits + chunk + bits/chunk + log2(dimension)`, using about
+/// `dimension * bits / 2 + 2 bits` additions.
+///
+/// The scalars are split into chunks of `chunk` bits. Each bit position's bases are summed in a
+/// balanced tree; the positions of a chunk are combined by Horner's rule, and so are the chunks.
+/// Each chunk is ready before the running sum needs it, so only the top chunk pays for its depth.
+pub fn build_low_depth<F: PrimeField>(target: Vec<F>, chunk: usize) -> VecAddChain {
+    assert!(chunk > 0);
+    let dimension = target.len();
+    let mut chain = VecAddChain {
+        dimension,
+        adds: vec![],
+        subs: vec![],
+    };
+    let scalars: Vec<Vec<bool>> = target.iter().map(|f| f.into_repr().to_bits_le()).collect();
+    let bits = bit_length(&scalars);
+    let columns: Vec<Option<usize>> = (0..bits)
+        .map(|j| {
+            balanced_sum(
+                &mut chain,
+                (0..dimension).filter(|i| scalars[*i][j]).collect(),
+            )
+        })
+        .collect();
+    let double = |chain: &mut VecAddChain, a: Option<usize>| a.map(|a| chain.push(a, a, false));
+    let add = |chain: &mut VecAddChain, a: Option<usize>, b: Option<usize>| match (a, b) {
+        (Some(a), Some(b)) => Some(chain.push(a, b, false)),
+        (a, None) => a,
+        (None, b) => b,
+    };
+    let mut acc = None;
+    for c in (0..bits.div_ceil(chunk)).rev() {
+        let positions = c * chunk..(c * chunk + chunk).min(bits);
+        let mut part = None;
+        for j in positions.clone().rev() {
+            part = double(&mut chain, part);
+            part = add(&mut chain, part, columns[j]);
+        }
+        for _ in positions {
+            acc = double(&mut chain, acc);
+        }
+        acc = add(&mut chain, acc, part);
+    }
+    chain.set_output(acc.unwrap());
+    chain
+}
+
+/// [build_low_depth], with the chunk width from [best_chunk].
+pub fn build_low_depth_auto<F: PrimeField>(target: Vec<F>) -> VecAddChain {
+    build_low_depth(target, best_chunk(F::size_in_bits()))
+}
+
+/// The bit length of the largest scalar in `scalars`. Panics if they are all zero.
+fn bit_length(scalars: &[Vec<bool>]) -> usize {
+    scalars
+        .iter()
+        .filter_map(|s| s.iter().rposition(|b| *b))
+        .max()
+        .expect("zero target")
+        + 1
+}
+
+/// Append the additions of `part`, a chain of the same dimension, returning the label of its output.
+fn append(chain: &mut VecAddChain, part: &VecAddChain) -> usize {
+    let base = chain.dimension + chain.adds.len();
+    let relabel = |l: usize| {
+        if l < part.dimension {
+            l
+        } else {
+            base + l - part.dimension
+        }
+    };
+    for (step, (a, b)) in part.adds.iter().enumerate() {
+        chain.push(relabel(*a), relabel(*b), part.is_sub(step));
+    }
+    relabel(part.dimension + part.adds.len() - 1)
+}
+
+/// Build a chain from the scalars' `width`-bit digits, rebalanced.
+///
+/// Each digit position gets a Fast Bos-Coster chain for its digits, and the positions are combined
+/// by Horner's rule, from the most significant. Only the top position's chain is on the longest
+/// path, so narrow digits give shallow chains, and wide ones give short chains: with `width` at
+/// least the scalar size, this is the Fast builder.
+pub fn build_windowed<F: PrimeField>(target: Vec<F>, width: usize) -> VecAddChain {
+    assert!(width > 0);
+    let scalars: Vec<Vec<bool>> = target.iter().map(|f| f.into_repr().to_bits_le()).collect();
+    let bits = bit_length(&scalars);
+    let mut chain = VecAddChain {
+        dimension: target.len(),
+        adds: vec![],
+        subs: vec![],
+    };
+    let mut acc: Option<usize> = None;
+    for c in (0..bits.div_ceil(width)).rev() {
+        let positions = c * width..(c * width + width).min(bits);
+        for _ in positions.clone() {
+            acc = acc.map(|a| chain.push(a, a, false));
+        }
+        let digits: Vec<ArkBigInt<F::BigInt>> = scalars
+            .iter()
+            .map(|s| ArkBigInt(F::BigInt::from_bits_le(&s[positions.clone()])))
+            .collect();
+        if digits.iter().all(|d| d.is_zero()) {
+            continue;
+        }
+        let part = append(&mut chain, &bos_coster_fast::build_chain_scalars(digits));
+        acc = Some(match acc {
+            Some(a) => chain.push(a, part, false),
+            None => part,
+        });
+    }
+    chain.set_output(acc.unwrap());
+    rebalance(&chain)
+}
+
+/// The digit widths that [build_chain] tries, for scalars of `bits` bits, widest first.
+pub fn widths(bits: usize) -> Vec<usize> {
+    let mut widths = vec![bits.max(1)];
+    while *widths.last().unwrap() > 1 {
+        let w = *widths.last().unwrap();
+        widths.push(w * 2 / 3);
+    }
+    widths
+}
+
+/// The additions-versus-depth tradeoff: a [build_windowed] chain for each of the [widths], and
+/// then a [build_low_depth_auto] chain, rebalanced.
+pub fn tradeoff<F: PrimeField>(target: &[F]) -> Vec<VecAddChain> {
+    let mut chains: Vec<VecAddChain> = widths(F::size_in_bits())
+        .into_iter()
+        .map(|w| build_windowed(target.to_vec(), w))
+        .collect();
+    chains.push(rebalance(&build_low_depth_auto(target.to_vec())));
+    chains
+}
+
+/// The shortest chain for `target` of depth at most `max_depth`, or `None` if the bound is too
+/// tight.
+///
+/// Searches the [tradeoff]: wider digits for [build_windowed] cost fewer additions but more depth.
+pub fn build_chain<F: PrimeField>(target: Vec<F>, max_depth: usize) -> Option<VecAddChain> {
+    tradeoff(&target)
+        .into_iter()
+        .filter(|chain| chain.depth() <= max_depth)
+        .min_by_key(|chain| chain.adds.len())
+}
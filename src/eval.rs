@@ -0,0 +1,75 @@
+use super::{ChainError, VecAddChain};
+use std::ops::{Add, Neg};
+
+/// An additive group without negation, which is all [crate::fixed_base::Table::bases] needs.
+///
+/// Implemented for anything that is `Clone + Add`: arkworks curve points (projective and affine),
+/// field elements, and integers.
+pub trait AddGroup: Clone {
+    fn group_add(&self, other: &Self) -> Self;
+}
+
+impl<T: Clone + Add<Output = T>> AddGroup for T {
+    fn group_add(&self, other: &Self) -> Self {
+        self.clone() + other.clone()
+    }
+}
+
+/// An additive group with negation, which a [VecAddChain] can be evaluated over.
+///
+/// Implemented for anything that is `Clone + Add + Neg`: arkworks curve points, field elements,
+/// and signed integers.
+pub trait SignedGroup: AddGroup {
+    fn group_sub(&self, other: &Self) -> Self;
+}
+
+impl<T: Clone + Add<Output = T> + Neg<Output = T>> SignedGroup for T {
+    fn group_sub(&self, other: &Self) -> Self {
+        self.clone() + -other.clone()
+    }
+}
+
+impl VecAddChain {
+    /// Evaluate the chain on `bases`, performing the additions and subtractions in order, and
+    /// return the last term.
+    ///
+    /// For an MSM, `bases` are the points and the chain's target is the scalars.
+    ///
+    /// Errors if `bases` does not have length `dimension`, or if the chain is malformed (see
+    /// [VecAddChain::validate_structure]). Panics only if the chain has no terms at all.
+    pub fn evaluate<G: SignedGroup>(&self, bases: &[G]) -> Result<G, ChainError> {
+        if bases.len() != self.dimension {
+            return Err(ChainError::DimensionMismatch {
+                chain: self.dimension,
+                target: bases.len(),
+            });
+        }
+        self.validate_structure()?;
+        Ok(self.evaluate_signed(bases))
+    }
+
+    /// Like [VecAddChain::evaluate], but panics where that errors.
+    pub fn evaluate_signed<G: SignedGroup>(&self, bases: &[G]) -> G {
+        self.evaluate_with(
+            bases,
+            |a, b, sub| {
+                if sub {
+                    a.group_sub(b)
+                } else {
+                    a.group_add(b)
+                }
+            },
+        )
+    }
+
+    pub(crate) fn evaluate_with<G: Clone>(&self, bases: &[G], add: impl Fn(&G, &G, bool) -> G) -> G {
+        assert_eq!(bases.len(), self.dimension);
+        let mut terms: Vec<G> = Vec::with_capacity(self.dimension + self.adds.len());
+        terms.extend_from_slice(bases);
+        for (step, (a, b)) in self.adds.iter().enumerate() {
+            let sum = add(&terms[*a], &terms[*b], self.is_sub(step));
+            terms.push(sum);
+        }
+        terms.pop().expect("empty chain")
+    }
+}
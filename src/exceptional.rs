@@ -0,0 +1,137 @@
+//! Finding the additions in a chain that incomplete addition formulas cannot handle.
+//!
+//! Incomplete (e.g. affine short Weierstrass) addition of `P` and `Q` fails when `P = Q`,
+//! `P = -Q`, or either operand is the identity. These functions report every addition in a chain
+//! that hits one of those cases, either for concrete base points or for symbolic bases.
+//!
+//! See also `analysis/dub/sim.sage`, which estimates how often these cases arise.
+use super::sparse::{self, SparseVec};
+use super::{ChainError, SignedGroup, VecAddChain};
+use ark_ff::{Field, Zero};
+use std::ops::Neg;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExceptionKind {
+    /// The addition is `(a, a)`: an explicit doubling.
+    Doubling,
+    /// The operands have different labels, but equal values.
+    Equal,
+    /// The operands are negatives of each other.
+    Opposite,
+    /// An operand is the identity.
+    Identity,
+}
+
+/// An addition that needs a complete formula.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Exception {
+    /// The index of the addition in `adds`.
+    pub step: usize,
+    pub kind: ExceptionKind,
+}
+
+/// Classify addition `step`, given whether an operand is the identity, and whether the operands
+/// are equal or opposite.
+///
+/// For a subtraction `a - b`, the operands of the underlying addition are `a` and `-b`.
+fn classify(
+    step: usize,
+    (a, b): (usize, usize),
+    sub: bool,
+    identity: bool,
+    equal: bool,
+    opposite: bool,
+) -> Option<Exception> {
+    let (equal, opposite) = if sub {
+        (opposite, equal)
+    } else {
+        (equal, opposite)
+    };
+    let kind = if a == b && !sub {
+        ExceptionKind::Doubling
+    } else if identity {
+        ExceptionKind::Identity
+    } else if equal {
+        ExceptionKind::Equal
+    } else if opposite {
+        ExceptionKind::Opposite
+    } else {
+        return None;
+    };
+    Some(Exception { step, kind })
+}
+
+/// Every exceptional addition when evaluating `chain` on the concrete `bases`.
+///
+/// Panics if `bases` does not have length `dimension`, or if the chain is malformed.
+pub fn find_exceptions<G>(chain: &VecAddChain, bases: &[G]) -> Vec<Exception>
+where
+    G: SignedGroup + Eq + Neg<Output = G> + Zero,
+{
+    assert_eq!(bases.len(), chain.dimension);
+    let mut terms: Vec<G> = bases.to_vec();
+    let mut exceptions = Vec::new();
+    for (step, (a, b)) in chain.adds.iter().enumerate() {
+        let (x, y) = (&terms[*a], &terms[*b]);
+        let sub = chain.is_sub(step);
+        exceptions.extend(classify(
+            step,
+            (*a, *b),
+            sub,
+            x.is_zero() || y.is_zero(),
+            x == y,
+            *x == -y.clone(),
+        ));
+        let sum = if sub { x.group_sub(y) } else { x.group_add(y) };
+        terms.push(sum);
+    }
+    exceptions
+}
+
+/// Every addition in `chain` that is exceptional for *some* choice of bases.
+///
+/// The bases are treated as independent elements of a group of order `|F|`, so two terms are
+/// equal exactly when their coefficient vectors agree modulo `|F|`.
+pub fn find_symbolic_exceptions<F: Field>(
+    chain: &VecAddChain,
+) -> Result<Vec<Exception>, ChainError> {
+    let bases = (0..chain.dimension).map(sparse::basis).collect();
+    symbolic_exceptions::<F>(chain, bases)
+}
+
+/// Like [find_symbolic_exceptions], but with known relations among the bases.
+///
+/// Base `i` is the combination `bases[i]` of some independent generators, over a group of order
+/// `|F|`.
+pub fn find_symbolic_exceptions_with<F: Field>(
+    chain: &VecAddChain,
+    bases: &[Vec<F>],
+) -> Result<Vec<Exception>, ChainError> {
+    if bases.len() != chain.dimension {
+        return Err(ChainError::DimensionMismatch {
+            chain: chain.dimension,
+            target: bases.len(),
+        });
+    }
+    let bases = bases.iter().map(|b| sparse::from_dense(b)).collect();
+    symbolic_exceptions::<F>(chain, bases)
+}
+
+fn symbolic_exceptions<F: Field>(
+    chain: &VecAddChain,
+    bases: Vec<SparseVec<F>>,
+) -> Result<Vec<Exception>, ChainError> {
+    let mut exceptions = Vec::new();
+    chain.sparse_fold::<F, _>(bases, |step, x, y, _| {
+        exceptions.extend(classify(
+            step,
+            chain.adds[step],
+            chain.is_sub(step),
+            x.is_empty() || y.is_empty(),
+            x == y,
+            sparse::is_opposite(x, y),
+        ));
+        Ok(())
+    })?;
+    Ok(exceptions)
+}
@@ -0,0 +1,115 @@
+//! Shortest vector addition chains for tiny targets, by exhaustive search.
+//!
+//! Meant as a ground truth for testing the heuristic builders, for dimensions up to about four and
+//! coefficients up to about 64. Only chains without subtractions are considered.
+//!
+//! By Olivos' duality, a shortest chain for `(n_1, ..., n_m)` (all nonzero) has `m - 1` more
+//! additions than a shortest addition sequence containing each `n_j`, and transposing the
+//! sequence's circuit gives the chain. Sequences are one-dimensional, so the search can keep them
+//! increasing, which prunes far better than searching chains directly.
+use super::VecAddChain;
+
+/// A shortest addition sequence (an increasing chain `1 = a_0 < a_1 < ...`, each the sum of two
+/// earlier ones) containing each of `outputs`, if there is one of at most `max_len` additions.
+///
+/// Found by iterative deepening. Panics if an output is zero.
+pub fn shortest_sequence(outputs: &[u64], max_len: usize) -> Option<Vec<u64>> {
+    assert!(outputs.iter().all(|o| *o > 0), "zero output");
+    let mut outputs = outputs.to_vec();
+    outputs.sort_unstable();
+    outputs.dedup();
+    let mut values = vec![1];
+    (0..=max_len).find_map(|len| {
+        if extend(&outputs, len, &mut values) {
+            Some(values.clone())
+        } else {
+            None
+        }
+    })
+}
+
+/// Extend the increasing sequence `values` to contain each of the sorted `outputs`, with at most
+/// `len` additions in total.
+fn extend(outputs: &[u64], len: usize, values: &mut Vec<u64>) -> bool {
+    let last = *values.last().unwrap();
+    let max = *outputs.last().unwrap();
+    // outputs below `last` can't be added any more
+    if outputs.iter().any(|o| *o < last && !values.contains(o)) {
+        return false;
+    }
+    if last == max {
+        return true;
+    }
+    // and the rest each need a step
+    let steps = len + 1 - values.len();
+    let missing = outputs.iter().filter(|o| **o > last).count();
+    if missing > steps || last << steps < max {
+        return false;
+    }
+    let mut sums: Vec<u64> = values
+        .iter()
+        .enumerate()
+        .flat_map(|(i, a)| values[i..].iter().map(move |b| a + b))
+        .filter(|s| *s > last && *s <= max)
+        .collect();
+    sums.sort_unstable_by(|a, b| b.cmp(a));
+    sums.dedup();
+    for s in sums {
+        values.push(s);
+        if extend(outputs, len, values) {
+            return true;
+        }
+        values.pop();
+    }
+    false
+}
+
+/// A shortest chain (without subtractions) for `target`, if there is one of at most `max_len`
+/// additions.
+///
+/// Returns `None` for a zero target, or a basis vector other than the last, which have no chains.
+pub fn shortest_chain(target: &[u64], max_len: usize) -> Option<VecAddChain> {
+    let dimension = target.len();
+    let nonzero = target.iter().filter(|t| **t > 0).count();
+    if nonzero == 0 || max_len + 1 < nonzero {
+        return None;
+    }
+    let outputs: Vec<u64> = target.iter().copied().filter(|t| *t > 0).collect();
+    let values = shortest_sequence(&outputs, max_len + 1 - nonzero)?;
+    // the operands of each step of the sequence
+    let steps: Vec<(usize, usize)> = (1..values.len())
+        .map(|i| {
+            (0..i)
+                .flat_map(|a| (a..i).map(move |b| (a, b)))
+                .find(|(a, b)| values[*a] + values[*b] == values[i])
+                .unwrap()
+        })
+        .collect();
+
+    // Transpose: each value of the sequence becomes the sum of the terms for the steps that use
+    // it, and of the bases whose coordinate it is. The term for 1 is then the target.
+    let mut summands: Vec<Vec<usize>> = vec![Vec::new(); values.len()];
+    for (j, t) in target.iter().enumerate().filter(|(_, t)| **t > 0) {
+        summands[values.binary_search(t).unwrap()].push(j);
+    }
+    let mut chain = VecAddChain {
+        dimension,
+        adds: vec![],
+        subs: vec![],
+    };
+    let mut terms = vec![0; values.len()];
+    for v in (0..values.len()).rev() {
+        let mut parts = summands[v].iter().copied();
+        let first = parts.next().expect("unused value in a shortest sequence");
+        terms[v] = parts.fold(first, |acc, p| chain.push(acc, p, false));
+        if v > 0 {
+            let (a, b) = steps[v - 1];
+            summands[a].push(terms[v]);
+            summands[b].push(terms[v]);
+        }
+    }
+    if chain.adds.is_empty() && terms[0] + 1 != dimension {
+        return None;
+    }
+    Some(chain)
+}
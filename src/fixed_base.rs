@@ -0,0 +1,97 @@
+//! Chains for fixed bases, with precomputed multiples.
+//!
+//! When the bases are fixed (Pedersen generators, say), multiples `2^s G_i` can be computed once
+//! and reused by every MSM. A [Table] lists the shifts `s` available for every base. Each scalar is
+//! split into digits at those shifts, and the MSM becomes one over the table entries with short
+//! scalars, which needs far fewer doublings.
+//!
+//! The chains that [build_chain] returns have one basis vector per table entry: the entry for
+//! shift `shifts()[j]` of base `i` has label `i * len() + j`. Evaluate them on [Table::bases].
+use super::{AddGroup, VecAddChain};
+use ark_ff::{BigInteger, PrimeField};
+
+/// The multiples `2^s G_i` precomputed for every base `G_i`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    shifts: Vec<usize>,
+}
+
+impl Table {
+    /// A table with the given shifts. Shift zero (the base itself) is always included.
+    pub fn new(mut shifts: Vec<usize>) -> Self {
+        shifts.push(0);
+        shifts.sort_unstable();
+        shifts.dedup();
+        Table { shifts }
+    }
+
+    /// A table with a shift every `step` bits, for scalars of `bits` bits.
+    pub fn every(step: usize, bits: usize) -> Self {
+        assert!(step > 0);
+        Table::new((0..bits).step_by(step).collect())
+    }
+
+    /// The shifts, in increasing order.
+    pub fn shifts(&self) -> &[usize] {
+        &self.shifts
+    }
+
+    /// The number of entries per base.
+    pub fn len(&self) -> usize {
+        self.shifts.len()
+    }
+
+    /// Whether the table has no entries (never: shift zero is always present).
+    pub fn is_empty(&self) -> bool {
+        self.shifts.is_empty()
+    }
+
+    /// The precomputed entries for `bases`, in label order.
+    pub fn bases<G: AddGroup>(&self, bases: &[G]) -> Vec<G> {
+        let mut out = Vec::with_capacity(bases.len() * self.len());
+        for b in bases {
+            let mut multiple = b.clone();
+            let mut shift = 0;
+            for s in &self.shifts {
+                for _ in shift..*s {
+                    multiple = multiple.group_add(&multiple);
+                }
+                shift = *s;
+                out.push(multiple.clone());
+            }
+        }
+        out
+    }
+
+    /// Split each scalar of `target` into its digits at the table's shifts, in label order.
+    pub fn expand<F: PrimeField>(&self, target: &[F]) -> Vec<F> {
+        let mut out = Vec::with_capacity(target.len() * self.len());
+        for t in target {
+            let bits = t.into_repr().to_bits_le();
+            for (j, lo) in self.shifts.iter().enumerate() {
+                let hi = self.shifts.get(j + 1).copied().unwrap_or(bits.len());
+                let digit = bits[(*lo).min(bits.len())..hi.min(bits.len())]
+                    .iter()
+                    .rev()
+                    .fold(F::zero(), |d, b| {
+                        if *b {
+                            d.double() + F::one()
+                        } else {
+                            d.double()
+                        }
+                    });
+                out.push(digit);
+            }
+        }
+        out
+    }
+}
+
+/// Build a chain for `target` over the entries of `table`, with `builder`.
+pub fn build_chain<F: PrimeField>(
+    target: Vec<F>,
+    table: &Table,
+    builder: impl FnOnce(Vec<F>) -> VecAddChain,
+) -> VecAddChain {
+    builder(table.expand(&target))
+}
@@ -0,0 +1,192 @@
+//! GLV decomposition: halving the scalars with an endomorphism.
+//!
+//! On curves with an efficient endomorphism `φ` that acts as multiplication by some `λ` (like
+//! BLS12-381 G1, where `φ(x, y) = (βx, y)`), a scalar `k` can be written `k1 + k2 λ`, so that
+//! `k P = k1 P + k2 φ(P)`. [Glv::decompose] finds short `k1, k2` (Gallant, Lambert and Vanstone):
+//! the pairs `(a, b)` with `a + b λ = 0 (mod r)` form a lattice, and subtracting a nearby lattice
+//! vector from `(k, 0)` leaves a short one. For the eigenvalue of an endomorphism, both halves have
+//! about half the bits of `r`, up to sign. Bos-Coster's cost scales with scalar size, so a chain for
+//! the `2n` half-size scalars is much shorter than one for the `n` full ones.
+use super::{SignedGroup, VecAddChain};
+use ark_ff::{FpParameters, PrimeField};
+use num_bigint::{BigInt, BigUint, Sign};
+use std::ops::Neg;
+
+/// An endomorphism eigenvalue `λ`, for decomposing scalars.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glv<F> {
+    lambda: F,
+    /// A reduced basis of the lattice of `(a, b)` with `a + b λ = 0 (mod r)`.
+    basis: [(BigInt, BigInt); 2],
+}
+
+/// A chain over the bases and their images under the endomorphism.
+///
+/// Base `i` has label `2i`, and its image has label `2i + 1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlvChain {
+    pub chain: VecAddChain,
+    /// The basis labels that need the endomorphism applied, in increasing order.
+    pub endomorphism: Vec<usize>,
+    /// The basis labels that need negating, in increasing order.
+    pub negated: Vec<usize>,
+}
+
+/// `a / b`, rounded to the nearest integer.
+fn round_div(a: &BigInt, b: &BigInt) -> BigInt {
+    let (a, b) = if b.sign() == Sign::Minus {
+        (-a, -b)
+    } else {
+        (a.clone(), b.clone())
+    };
+    // floor((2a + b) / 2b); division truncates towards zero
+    let (n, d) = (a * 2u32 + &b, b * 2u32);
+    let q = &n / &d;
+    if n.sign() == Sign::Minus && &q * &d != n {
+        q - 1
+    } else {
+        q
+    }
+}
+
+/// A short basis of the lattice of `(a, b)` with `a + b λ = 0 (mod r)`.
+///
+/// The extended Euclidean algorithm on `r` and `λ` gives remainders `r_i = s_i r + t_i λ`, so each
+/// `(r_i, -t_i)` is in the lattice. Around `r_i ≈ sqrt(r)` both coordinates are small.
+fn short_basis(r: &BigInt, lambda: &BigInt) -> [(BigInt, BigInt); 2] {
+    let step = |(r0, t0): &(BigInt, BigInt), (r1, t1): &(BigInt, BigInt)| {
+        let q = r0 / r1;
+        (r0 - &q * r1, t0 - &q * t1)
+    };
+    // consecutive (r_i, t_i)
+    let mut prev = (r.clone(), BigInt::from(0));
+    let mut cur = (lambda.clone(), BigInt::from(1));
+    while &cur.0 * &cur.0 >= *r {
+        let next = step(&prev, &cur);
+        prev = std::mem::replace(&mut cur, next);
+    }
+    // cur is the first remainder below sqrt(r); pair it with the shorter neighbour
+    let next = step(&prev, &cur);
+    let norm = |(r, t): &(BigInt, BigInt)| r * r + t * t;
+    let other = if norm(&prev) <= norm(&next) {
+        prev
+    } else {
+        next
+    };
+    [(cur.0, -cur.1), (other.0, -other.1)]
+}
+
+/// `f` as a magnitude and sign, taking `f` as negative when `-f` is smaller.
+fn signed<F: PrimeField>(f: F) -> (F, bool) {
+    if (-f).into_repr() < f.into_repr() {
+        (-f, true)
+    } else {
+        (f, false)
+    }
+}
+
+impl<F: PrimeField> Glv<F> {
+    /// Panics if `lambda` is zero or one.
+    ///
+    /// Any other `lambda` gives valid decompositions, but only an endomorphism eigenvalue (a root
+    /// of a small polynomial, like `λ^2 + λ + 1`) gives short ones.
+    pub fn new(lambda: F) -> Self {
+        let lambda_int: BigUint = lambda.into_repr().into();
+        assert!(lambda_int.bits() > 1, "trivial eigenvalue");
+        let r: BigUint = F::Params::MODULUS.into();
+        let basis = short_basis(&r.into(), &lambda_int.into());
+        Glv { lambda, basis }
+    }
+
+    pub fn lambda(&self) -> F {
+        self.lambda
+    }
+
+    /// Split `k` into `(k1, k2)` with `k = k1 + k2 λ`, where `k1` and `k2` are short up to sign.
+    ///
+    /// Writes `(k, 0)` in the lattice basis, rounds the coefficients to the nearest lattice vector
+    /// (Babai's rounding), and returns the difference.
+    pub fn decompose(&self, k: &F) -> (F, F) {
+        let k: BigUint = k.into_repr().into();
+        let k = BigInt::from(k);
+        let [(a1, b1), (a2, b2)] = &self.basis;
+        let det = a1 * b2 - a2 * b1;
+        let c1 = round_div(&(&k * b2), &det);
+        let c2 = round_div(&-(&k * b1), &det);
+        let k1 = k - &c1 * a1 - &c2 * a2;
+        let k2 = -(c1 * b1) - c2 * b2;
+        let to_field = |x: BigInt| {
+            let (sign, magnitude) = x.into_parts();
+            let f = F::from(magnitude);
+            if sign == Sign::Minus {
+                -f
+            } else {
+                f
+            }
+        };
+        (to_field(k1), to_field(k2))
+    }
+
+    /// Decompose each scalar of `target`, in label order, as magnitudes and signs.
+    fn split(&self, target: &[F]) -> Vec<(F, bool)> {
+        target
+            .iter()
+            .flat_map(|k| {
+                let (k1, k2) = self.decompose(k);
+                vec![signed(k1), signed(k2)]
+            })
+            .collect()
+    }
+
+    /// The magnitudes of the decomposed scalars of `target`, in label order: the target of a
+    /// [GlvChain] for `target`.
+    pub fn expand(&self, target: &[F]) -> Vec<F> {
+        self.split(target).into_iter().map(|(f, _)| f).collect()
+    }
+
+    /// Build a chain for `target` over the bases and their images, with `builder`.
+    pub fn build_chain(
+        &self,
+        target: Vec<F>,
+        builder: impl FnOnce(Vec<F>) -> VecAddChain,
+    ) -> GlvChain {
+        let endomorphism = (0..target.len()).map(|i| 2 * i + 1).collect();
+        let (expanded, signs): (Vec<F>, Vec<bool>) = self.split(&target).into_iter().unzip();
+        let negated = (0..signs.len()).filter(|i| signs[*i]).collect();
+        GlvChain {
+            chain: builder(expanded),
+            endomorphism,
+            negated,
+        }
+    }
+}
+
+impl GlvChain {
+    /// The basis for the chain: each of `bases`, followed by its image under `endomorphism`, with
+    /// the [GlvChain::negated] labels negated.
+    pub fn bases<G: Clone + Neg<Output = G>>(
+        &self,
+        bases: &[G],
+        endomorphism: impl Fn(&G) -> G,
+    ) -> Vec<G> {
+        assert_eq!(2 * bases.len(), self.chain.dimension);
+        let mut out = Vec::with_capacity(self.chain.dimension);
+        for b in bases {
+            out.push(b.clone());
+            out.push(endomorphism(b));
+        }
+        for label in &self.negated {
+            out[*label] = -out[*label].clone();
+        }
+        out
+    }
+
+    /// Evaluate the chain on `bases`, applying `endomorphism` to the labels that need it.
+    pub fn evaluate_signed<G: SignedGroup + Neg<Output = G>>(
+        &self,
+        bases: &[G],
+        endomorphism: impl Fn(&G) -> G,
+    ) -> G {
+        self.chain.evaluate_signed(&self.bases(bases, endomorphism))
+    }
+}
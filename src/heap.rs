@@ -0,0 +1,205 @@
+//! Priority queues tuned for the Bos-Coster inner loop.
+//!
+//! Each Bos-Coster step reads the largest two entries and then changes them. With
+//! [std::collections::BinaryHeap] that is two pops and up to three pushes, and for large targets
+//! each is a chain of cache misses: the difference of the two largest entries is usually tiny, so
+//! it sinks to the bottom of the heap.
+//!
+//! [RadixHeap] instead buckets entries by the highest bit in which their [Keyed::key] differs from
+//! a threshold, so a push is an append, and buckets are redistributed with sequential scans when
+//! the entries above the threshold run out. The few entries at or above the threshold are kept in
+//! a [Heap] that is updated in place; since Bos-Coster never pushes an entry above the largest,
+//! there are few of them.
+
+/// An entry with a cheap key that orders like the entry, except that it may tie unequal ones.
+pub(crate) trait Keyed: Ord {
+    fn key(&self) -> u64;
+}
+
+/// A binary max-heap that can read and replace its second largest entry in place.
+pub(crate) struct Heap<T> {
+    data: Vec<T>,
+}
+
+impl<T: Ord> Heap<T> {
+    pub fn new() -> Self {
+        Heap { data: Vec::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn push(&mut self, item: T) {
+        self.data.push(item);
+        self.sift_up(self.data.len() - 1);
+    }
+
+    /// The index of the second largest entry. Panics if there are fewer than two.
+    pub fn second(&self) -> usize {
+        assert!(self.data.len() > 1);
+        if self.data.len() > 2 && self.data[2] > self.data[1] {
+            2
+        } else {
+            1
+        }
+    }
+
+    pub fn get(&self, i: usize) -> &T {
+        &self.data[i]
+    }
+
+    /// Replace entry `i` with `item`, returning the old entry.
+    pub fn replace(&mut self, i: usize, item: T) -> T {
+        let old = std::mem::replace(&mut self.data[i], item);
+        self.fix(i);
+        old
+    }
+
+    /// Remove entry `i`.
+    pub fn remove(&mut self, i: usize) -> T {
+        let old = self.data.swap_remove(i);
+        if i < self.data.len() {
+            self.fix(i);
+        }
+        old
+    }
+
+    /// Move entry `i`, the only one out of place, to where it belongs.
+    fn fix(&mut self, i: usize) {
+        if self.sift_up(i) == i {
+            self.sift_down(i);
+        }
+    }
+
+    fn sift_up(&mut self, mut i: usize) -> usize {
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if self.data[i] <= self.data[parent] {
+                break;
+            }
+            self.data.swap(i, parent);
+            i = parent;
+        }
+        i
+    }
+
+    fn sift_down(&mut self, mut i: usize) {
+        let len = self.data.len();
+        loop {
+            let left = 2 * i + 1;
+            if left >= len {
+                break;
+            }
+            let right = left + 1;
+            let child = if right < len && self.data[right] > self.data[left] {
+                right
+            } else {
+                left
+            };
+            if self.data[child] <= self.data[i] {
+                break;
+            }
+            self.data.swap(i, child);
+            i = child;
+        }
+    }
+}
+
+/// The bits in a digit of a [RadixHeap] key.
+const DIGIT: u32 = 8;
+const DIGITS: usize = (64 / DIGIT) as usize;
+const BUCKETS: usize = DIGITS << DIGIT;
+
+/// A max-queue, fast when entries are rarely pushed above the largest.
+///
+/// Entries with keys at or above `threshold` are in `top`. The rest are in `buckets`, by the
+/// highest digit `d` in which their key differs from `threshold`, then by their value `v` of that
+/// digit, at index `(d << DIGIT) + v`: larger indices hold larger keys, so each entry moves at
+/// most `DIGITS` times. Indices (from [RadixHeap::second], and `0` for the largest) refer to
+/// `top`, which [RadixHeap::fill] keeps at two or more entries.
+pub(crate) struct RadixHeap<T> {
+    threshold: u64,
+    top: Heap<T>,
+    buckets: Vec<Vec<T>>,
+    /// A bit for each bucket, set when it is not empty.
+    occupied: [u64; BUCKETS / 64],
+    len: usize,
+}
+
+impl<T: Keyed> RadixHeap<T> {
+    pub fn new() -> Self {
+        RadixHeap {
+            threshold: u64::MAX,
+            top: Heap::new(),
+            buckets: (0..BUCKETS).map(|_| Vec::new()).collect(),
+            occupied: [0; BUCKETS / 64],
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn push(&mut self, item: T) {
+        self.len += 1;
+        self.insert(item);
+    }
+
+    /// Make sure the largest two entries are in `top`.
+    pub fn fill(&mut self) {
+        while self.top.len() < 2 {
+            let b = match self.occupied.iter().rposition(|w| *w != 0) {
+                Some(w) => 64 * w + 63 - self.occupied[w].leading_zeros() as usize,
+                None => return,
+            };
+            self.occupied[b / 64] &= !(1 << (b % 64));
+            let bucket = std::mem::take(&mut self.buckets[b]);
+            self.threshold = bucket.iter().map(|item| item.key()).max().unwrap();
+            for item in bucket {
+                self.insert(item);
+            }
+        }
+    }
+
+    /// The index of the second largest entry. Panics if there are fewer than two in `top`.
+    pub fn second(&self) -> usize {
+        self.top.second()
+    }
+
+    pub fn get(&self, i: usize) -> &T {
+        self.top.get(i)
+    }
+
+    /// Replace entry `i` with `item`, returning the old entry.
+    pub fn replace(&mut self, i: usize, item: T) -> T {
+        if item.key() >= self.threshold {
+            self.top.replace(i, item)
+        } else {
+            let old = self.top.remove(i);
+            self.insert(item);
+            old
+        }
+    }
+
+    /// Remove entry `i`.
+    pub fn remove(&mut self, i: usize) -> T {
+        self.len -= 1;
+        self.top.remove(i)
+    }
+
+    fn insert(&mut self, item: T) {
+        let key = item.key();
+        if key >= self.threshold {
+            self.top.push(item);
+        } else {
+            let d = (key ^ self.threshold).leading_zeros() / DIGIT;
+            let shift = 64 - DIGIT * (d + 1);
+            let v = (key >> shift) as usize & ((1 << DIGIT) - 1);
+            let b = ((d as usize) << DIGIT) + v;
+            self.occupied[b / 64] |= 1 << (b % 64);
+            self.buckets[b].push(item);
+        }
+    }
+}
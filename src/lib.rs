@@ -3,16 +3,52 @@ use ark_ff::Field;
 pub mod bos_coster;
 pub mod bos_coster_fast;
 pub mod bos_coster_many;
+#[cfg(feature = "parallel")]
+pub mod bos_coster_par;
+pub mod bos_coster_signed;
+pub mod bounds;
+pub mod chunked;
+pub mod complete;
+pub mod dag;
+pub mod depth;
+pub mod eval;
+pub mod exceptional;
+pub mod exhaustive;
+pub mod fixed_base;
+#[cfg(feature = "bigint")]
+pub mod glv;
+mod heap;
+pub mod multi;
+pub mod optimize;
+pub mod pippenger;
+#[cfg(feature = "r1cs")]
+pub mod r1cs;
+pub mod scalar;
+pub mod scalar_chain;
+pub mod schedule;
+pub mod serialize;
+pub mod straus;
+mod sparse;
+pub mod stream;
+pub mod validate;
+
+pub use eval::{AddGroup, SignedGroup};
+pub use multi::MultiVecAddChain;
+pub use serialize::DecodeError;
+pub use validate::ChainError;
 
 /// A vector addition chain
 ///
 /// Encodes a procedure for computing a target vector
-/// from basis vectors using binary additions
+/// from basis vectors using binary additions (and, optionally, subtractions)
 ///
 /// Each computed term is labelled.
 ///
 /// Labels start at 0 and go up.
-#[derive(Debug)]
+///
+/// See [serialize] for binary and text encodings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VecAddChain {
     /// The dimension of the target vector (and number of basis vectors).
     ///
@@ -24,38 +60,78 @@ pub struct VecAddChain {
     ///
     /// Summand of index `i` has label `dimension + i`.
     pub adds: Vec<(usize, usize)>,
+    /// The indices of the additions that are subtractions, in increasing order.
+    ///
+    /// If `i` is listed, then `adds[i] = (a, b)` computes `a - b`, rather than `a + b`.
+    pub subs: Vec<usize>,
 }
 
-pub fn check_chain<F: Field>(chain: &VecAddChain, target: &[F]) {
-    let mut vecs: Vec<Vec<F>> = Vec::new();
-    assert_eq!(chain.dimension, target.len());
-    let add_vecs = |a: &[F], b: &[F]| {
-        assert_eq!(a.len(), b.len());
-        a.iter().zip(b).map(|(a, b)| *a + b).collect::<Vec<F>>()
-    };
-    for i in 0..chain.dimension {
-        vecs.push(vec![F::zero(); chain.dimension]);
-        vecs.last_mut().unwrap()[i] = F::one();
+impl VecAddChain {
+    /// Whether addition `step` is a subtraction.
+    pub fn is_sub(&self, step: usize) -> bool {
+        self.subs.binary_search(&step).is_ok()
+    }
+
+    /// Append `a + b` (or `a - b`, if `sub`) to the chain, returning its label.
+    pub fn push(&mut self, a: usize, b: usize, sub: bool) -> usize {
+        if sub {
+            self.subs.push(self.adds.len());
+        }
+        self.adds.push((a, b));
+        self.dimension + self.adds.len() - 1
+    }
+
+    /// Make the term labelled `output` the result of the chain (its last term).
+    ///
+    /// Drops the additions after `output`, which it cannot depend on. A basis vector other than the
+    /// last is recomputed as `2 output - output`, so any builder may emit that one subtraction.
+    pub fn set_output(&mut self, output: usize) {
+        if output < self.dimension {
+            self.adds.clear();
+            self.subs.clear();
+            if output + 1 < self.dimension {
+                let double = self.push(output, output, false);
+                self.push(double, output, true);
+            }
+        } else {
+            let n_adds = output - self.dimension + 1;
+            self.adds.truncate(n_adds);
+            self.subs.retain(|s| *s < n_adds);
+        }
     }
-    for (a, b) in &chain.adds {
-        let sum = add_vecs(&vecs[*a], &vecs[*b]);
-        vecs.push(sum);
+}
+
+/// Panics if `chain` does not compute `target`.
+///
+/// See [VecAddChain::validate] for a non-panicking version.
+pub fn check_chain<F: Field>(chain: &VecAddChain, target: &[F]) {
+    if let Err(e) = chain.validate(target) {
+        panic!("invalid chain: {}", e);
     }
-    assert_eq!(vecs.last().unwrap(), target);
 }
 
 pub type ChainBuilder<F> = fn(target: Vec<F>) -> VecAddChain;
 
 #[cfg(test)]
 mod tests {
+    use super::exceptional::{self, Exception, ExceptionKind};
+    #[cfg(feature = "bigint")]
+    use super::glv;
     use super::{
-        bos_coster, bos_coster_fast, bos_coster_many, check_chain, VecAddChain,
+        bos_coster, bos_coster_fast, bos_coster_many, bos_coster_signed, bounds, check_chain,
+        chunked, complete, dag, depth, exhaustive, fixed_base, multi, optimize, pippenger, scalar,
+        scalar_chain, straus, stream, ChainError, DecodeError, VecAddChain,
     };
-    use ark_bls12_381::Fr;
-    use ark_ff::PrimeField;
+    use ark_bls12_381::{Fr, G1Projective};
+    use ark_ec::ProjectiveCurve;
+    use ark_ff::{BigInteger, Field, One, PrimeField, UniformRand, Zero};
+    #[cfg(feature = "bigint")]
+    use num_bigint::BigUint;
 
-    fn test_on_target<F: PrimeField>(target: Vec<F>) {
-        let builders: Vec<(&str, Box<dyn Fn(Vec<F>) -> VecAddChain>)> = vec![
+    type Builder<F> = Box<dyn Fn(Vec<F>) -> VecAddChain>;
+
+    fn builders<F: PrimeField>() -> Vec<(&'static str, Builder<F>)> {
+        vec![
             (
                 "shallow",
                 Box::new(bos_coster::build_chain::<F, bos_coster::UseShallow>),
@@ -73,12 +149,49 @@ mod tests {
                 Box::new(bos_coster_many::build_chain::<F, bos_coster::UseDeep>),
             ),
             ("fast", Box::new(bos_coster_fast::build_chain::<F>)),
-        ];
-        for (name, builder) in builders {
+            ("signed", Box::new(bos_coster_signed::build_chain::<F>)),
+            ("pippenger", Box::new(pippenger::build_chain_auto::<F>)),
+            ("pippenger-3", Box::new(|t| pippenger::build_chain(t, 3))),
+            ("straus", Box::new(straus::build_chain_auto::<F>)),
+            ("straus-2", Box::new(|t| straus::build_chain(t, 2))),
+            (
+                "complete-fast",
+                Box::new(|t| complete::build_chain(t, bos_coster_fast::build_chain::<F>).chain),
+            ),
+            ("low-depth", Box::new(depth::build_low_depth_auto::<F>)),
+            ("low-depth-3", Box::new(|t| depth::build_low_depth(t, 3))),
+            (
+                "rebalanced-fast",
+                Box::new(|t| depth::rebalance(&bos_coster_fast::build_chain::<F>(t))),
+            ),
+            (
+                "rebalanced-signed",
+                Box::new(|t| depth::rebalance(&bos_coster_signed::build_chain::<F>(t))),
+            ),
+        ]
+    }
+
+    /// Run `test` on each of [builders], naming the builder first.
+    fn each_builder<F: PrimeField>(mut test: impl FnMut(&Builder<F>)) {
+        for (name, builder) in builders() {
             println!("Running: {}", name);
+            test(&builder);
+        }
+    }
+
+    /// The multi-scalar multiplication, one scalar multiplication at a time.
+    fn naive_msm(scalars: &[Fr], bases: &[G1Projective]) -> G1Projective {
+        scalars
+            .iter()
+            .zip(bases)
+            .fold(G1Projective::zero(), |acc, (s, b)| acc + b.mul(s.into_repr()))
+    }
+
+    fn test_on_target<F: PrimeField>(target: Vec<F>) {
+        each_builder(|builder| {
             let chain = builder(target.clone());
             check_chain(&chain, &target);
-        }
+        });
     }
 
     fn double_odd<F: PrimeField>() {
@@ -99,7 +212,18 @@ mod tests {
 
     fn test_twos<F: PrimeField>() {
         for n in 1..100 {
-            test_on_target::<F>(vec![F::from(2 as u32); n]);
+            test_on_target::<F>(vec![F::from(2u32); n]);
+        }
+    }
+
+    fn test_units<F: PrimeField>() {
+        // basis vectors, including ones other than the last
+        for n in 1..5 {
+            for i in 0..n {
+                let mut target = vec![F::zero(); n];
+                target[i] = F::one();
+                test_on_target::<F>(target);
+            }
         }
     }
 
@@ -126,6 +250,11 @@ mod tests {
         test_twos::<Fr>();
     }
 
+    #[test]
+    fn test_units_bls12_381() {
+        test_units::<Fr>();
+    }
+
     #[test]
     fn test_incr_bls12_381() {
         test_incr::<Fr>();
@@ -147,6 +276,876 @@ mod tests {
         test_single::<Fr>();
     }
 
+    #[test]
+    fn test_shared_terms() {
+        // 4 = 2 + 2 reaches the doubling through both children; it is computed once
+        let target = vec![Fr::from(4u32)];
+        let chains = vec![
+            bos_coster::build_chain::<Fr, bos_coster::UseShallow>(target.clone()),
+            bos_coster::build_chain::<Fr, bos_coster::UseDeep>(target.clone()),
+            bos_coster_many::build_chain::<Fr, bos_coster::UseShallow>(target.clone()),
+            bos_coster_many::build_chain::<Fr, bos_coster::UseDeep>(target.clone()),
+        ];
+        for chain in chains {
+            check_chain(&chain, &target);
+            assert_eq!(chain.adds, vec![(0, 0), (1, 1)]);
+        }
+    }
+
+    #[test]
+    fn test_msm_bls12_381_g1() {
+        let rng = &mut ark_std::test_rng();
+        let scalars: Vec<Fr> = (0..20).map(|_| Fr::rand(rng)).collect();
+        let bases: Vec<G1Projective> = (0..20).map(|_| G1Projective::rand(rng)).collect();
+        let msm = naive_msm(&scalars, &bases);
+        let affine_bases = G1Projective::batch_normalization_into_affine(&bases);
+        each_builder(|builder| {
+            let chain = builder(scalars.clone());
+            assert_eq!(chain.evaluate_signed(&bases), msm);
+            assert_eq!(chain.evaluate_signed(&affine_bases), msm.into_affine());
+            let field_sum: Fr = scalars.iter().map(|s| s.square()).sum();
+            assert_eq!(chain.evaluate_signed(&scalars), field_sum);
+            assert_eq!(chain.evaluate(&bases), Ok(msm));
+        });
+    }
+
+    #[test]
+    fn test_evaluate_unit() {
+        // an addition-only builder outputs a basis vector other than the last with a subtraction
+        let chain = bos_coster_fast::build_chain(vec![Fr::one(), Fr::zero()]);
+        assert_eq!(chain.subs.len(), 1);
+        assert_eq!(chain.evaluate(&[5i64, 7]), Ok(5));
+        let sub = VecAddChain {
+            dimension: 2,
+            adds: vec![(0, 0), (2, 1)],
+            subs: vec![1],
+        };
+        assert_eq!(sub.evaluate(&[5i64, 7]), Ok(3));
+        assert_eq!(
+            sub.evaluate(&[5i64]),
+            Err(ChainError::DimensionMismatch {
+                chain: 2,
+                target: 1
+            })
+        );
+        let forward = VecAddChain {
+            dimension: 2,
+            adds: vec![(0, 3), (2, 1)],
+            subs: vec![1],
+        };
+        assert_eq!(
+            forward.evaluate(&[5i64, 7]),
+            Err(ChainError::ForwardReference { step: 0, label: 3 })
+        );
+
+        // a signed chain subtracts more than the final undo (most often for few scalars)
+        let rng = &mut ark_std::test_rng();
+        let scalars: Vec<Fr> = (0..5).map(|_| Fr::rand(rng)).collect();
+        let bases: Vec<G1Projective> = (0..5).map(|_| G1Projective::rand(rng)).collect();
+        let signed = bos_coster_signed::build_chain(scalars.clone());
+        assert!(signed.subs.len() > 1);
+        assert_eq!(signed.evaluate(&bases), Ok(naive_msm(&scalars, &bases)));
+    }
+
+    #[test]
+    fn test_exceptions() {
+        let chain = VecAddChain {
+            dimension: 2,
+            adds: vec![(0, 0), (0, 1), (2, 3)],
+            subs: vec![],
+        };
+        let ex = |step, kind| Exception { step, kind };
+        assert_eq!(
+            exceptional::find_symbolic_exceptions::<Fr>(&chain),
+            Ok(vec![ex(0, ExceptionKind::Doubling)])
+        );
+        let opposite = vec![
+            ex(0, ExceptionKind::Doubling),
+            ex(1, ExceptionKind::Opposite),
+            ex(2, ExceptionKind::Identity),
+        ];
+        assert_eq!(
+            exceptional::find_symbolic_exceptions_with(&chain, &[vec![Fr::one()], vec![-Fr::one()]]),
+            Ok(opposite.clone())
+        );
+        let p = G1Projective::rand(&mut ark_std::test_rng());
+        assert_eq!(exceptional::find_exceptions(&chain, &[p, -p]), opposite);
+    }
+
+    #[test]
+    fn test_exceptions_concrete_are_symbolic() {
+        let rng = &mut ark_std::test_rng();
+        let scalars: Vec<Fr> = (0..10).map(|_| Fr::rand(rng)).collect();
+        let bases: Vec<G1Projective> = (0..10).map(|_| G1Projective::rand(rng)).collect();
+        each_builder(|builder| {
+            let chain = builder(scalars.clone());
+            let symbolic = exceptional::find_symbolic_exceptions::<Fr>(&chain).unwrap();
+            for e in exceptional::find_exceptions(&chain, &bases) {
+                assert!(symbolic.contains(&e));
+            }
+        });
+    }
+
+    #[test]
+    fn test_bounds() {
+        let f = |v: &[i64]| -> Vec<Fr> {
+            v.iter()
+                .map(|c| {
+                    if *c < 0 {
+                        -Fr::from(-c as u64)
+                    } else {
+                        Fr::from(*c as u64)
+                    }
+                })
+                .collect()
+        };
+        assert_eq!(bounds::lower_bound(&f(&[0, 0])), 0);
+        assert_eq!(bounds::lower_bound(&f(&[1, 0])), 0);
+        assert_eq!(bounds::lower_bound(&f(&[1, 1, 1])), 2);
+        assert_eq!(bounds::lower_bound(&f(&[3, 3])), 3);
+        assert_eq!(bounds::lower_bound(&f(&[-4, 1])), 3);
+        assert_eq!(bounds::lower_bound(&f(&[607])), 10);
+        assert_eq!(bounds::counting_bound(1, 1, 0), 0);
+        // all-zero targets have no bits
+        assert_eq!(bounds::counting_bound(3, 0, 0), 0);
+        assert!(bounds::counting_bound(20, 255, 10) > bounds::counting_bound(10, 255, 10));
+
+        let rng = &mut ark_std::test_rng();
+        let target: Vec<Fr> = (0..20).map(|_| Fr::rand(rng)).collect();
+        let bound = bounds::lower_bound(&target);
+        assert!(bound >= Fr::size_in_bits() - 8 + 19);
+        let counting = bounds::counting_bound(20, Fr::size_in_bits(), 10);
+        assert!(counting > bound);
+        each_builder(|builder| {
+            assert!(builder(target.clone()).adds.len() >= counting);
+        });
+    }
+
+    #[test]
+    fn test_dag() {
+        let mut dag = dag::Dag::new();
+        let (a, b) = (dag.basis(0), dag.basis(1));
+        let ab = dag.add(a.clone(), b.clone());
+        let double = dag.add(ab.clone(), ab.clone());
+        // repeats, in either order, are shared
+        assert!(dag.add(b.clone(), a.clone()) == ab);
+        assert!(dag.basis(1) == b);
+        let out = dag.add(double, b);
+        assert_eq!(dag.len(), 5);
+        assert_eq!(out.depth, 4);
+        let chain = dag.chain(3, out);
+        assert_eq!(chain.adds, vec![(0, 1), (3, 3), (1, 4)]);
+        assert_eq!(chain.evaluate(&[1i64, 10, 100]), Ok(2 * (1 + 10) + 10));
+    }
+
+    #[test]
+    fn test_chunked() {
+        let rng = &mut ark_std::test_rng();
+        let mut target: Vec<Fr> = (0..20).map(|_| Fr::rand(rng)).collect();
+        // a zero chunk
+        for t in &mut target[5..10] {
+            *t = Fr::zero();
+        }
+        each_builder(|builder| {
+            for chunk in [1, 3, 5, 7, 20, 100] {
+                check_chain(
+                    &chunked::build_chain(target.clone(), chunk, builder),
+                    &target,
+                );
+            }
+        });
+        #[cfg(feature = "parallel")]
+        for chunk in [1, 3, 5, 7, 20, 100] {
+            check_chain(
+                &chunked::build_chain_par(target.clone(), chunk, bos_coster_fast::build_chain),
+                &target,
+            );
+        }
+        // a chunk whose chain is a single basis vector
+        let mut unit = vec![Fr::zero(); 10];
+        unit[3] = Fr::one();
+        check_chain(
+            &chunked::build_chain(unit.clone(), 2, bos_coster_fast::build_chain),
+            &unit,
+        );
+    }
+
+    #[test]
+    fn test_fixed_base() {
+        let rng = &mut ark_std::test_rng();
+        let scalars: Vec<Fr> = (0..8).map(|_| Fr::rand(rng)).collect();
+        let bases: Vec<G1Projective> = (0..8).map(|_| G1Projective::rand(rng)).collect();
+        let msm = naive_msm(&scalars, &bases);
+        let bits = Fr::size_in_bits();
+        for table in [
+            fixed_base::Table::new(vec![]),
+            fixed_base::Table::new(vec![100, 3, 100]),
+            fixed_base::Table::every(16, bits),
+            fixed_base::Table::every(1, bits),
+        ] {
+            let expanded = table.expand(&scalars);
+            assert_eq!(expanded.len(), scalars.len() * table.len());
+            let table_bases = table.bases(&bases);
+            each_builder(|builder| {
+                let chain = fixed_base::build_chain(scalars.clone(), &table, builder);
+                check_chain(&chain, &expanded);
+                assert_eq!(chain.evaluate_signed(&table_bases), msm);
+            });
+        }
+        // with a shift every 16 bits, a table saves most of the doublings
+        let table = fixed_base::Table::every(16, bits);
+        let plain = bos_coster_fast::build_chain(scalars.clone()).adds.len();
+        let fixed = fixed_base::build_chain(scalars, &table, bos_coster_fast::build_chain)
+            .adds
+            .len();
+        assert!(fixed < plain);
+    }
+
+    #[test]
+    #[cfg(feature = "bigint")]
+    fn test_glv() {
+        use ark_bls12_381::{Fq, G1Affine};
+        use ark_ec::AffineCurve;
+        use ark_ff::SquareRootField;
+        // z^2 - 1 for the BLS parameter z, a cube root of unity
+        let lambda = Fr::from(0xd201000000010000u64).square() - Fr::one();
+        assert!((lambda.square() + lambda + Fr::one()).is_zero());
+        let glv = glv::Glv::new(lambda);
+        let rng = &mut ark_std::test_rng();
+        let scalars: Vec<Fr> = (0..8).map(|_| Fr::rand(rng)).collect();
+        let short = |f: Fr| f.into_repr().min((-f).into_repr()).num_bits() as usize;
+        // both cube roots give halves of about half the bits of r, up to sign
+        let half = Fr::size_in_bits().div_ceil(2) + 1;
+        for root in &[lambda, -lambda - Fr::one()] {
+            let glv = glv::Glv::new(*root);
+            for k in &scalars {
+                let (k1, k2) = glv.decompose(k);
+                assert_eq!(k1 + k2 * root, *k);
+                assert!(short(k1) <= half && short(k2) <= half);
+            }
+        }
+        // other eigenvalues still decompose, if not into short halves
+        let other = glv::Glv::new(Fr::rand(rng));
+        for k in &scalars {
+            let (k1, k2) = other.decompose(k);
+            assert_eq!(k1 + k2 * other.lambda(), *k);
+        }
+
+        // the endomorphism is (x, y) -> (beta x, y) for a cube root of unity beta in Fq
+        let sqrt = (-Fq::from(3u32)).sqrt().unwrap();
+        let g = G1Projective::prime_subgroup_generator().into_affine();
+        let endomorphism = [sqrt, -sqrt]
+            .iter()
+            .map(|s| (*s - Fq::one()) / Fq::from(2u32))
+            .map(|beta| move |p: &G1Affine| G1Affine::new(beta * p.x, p.y, p.infinity))
+            .find(|phi| phi(&g) == g.mul(lambda.into_repr()).into_affine())
+            .unwrap();
+        let bases: Vec<G1Projective> = (0..8).map(|_| G1Projective::rand(rng)).collect();
+        let affine_bases = G1Projective::batch_normalization_into_affine(&bases);
+        let msm = naive_msm(&scalars, &bases);
+        each_builder(|builder| {
+            let chain = glv.build_chain(scalars.clone(), builder);
+            assert_eq!(chain.endomorphism, vec![1, 3, 5, 7, 9, 11, 13, 15]);
+            check_chain(&chain.chain, &glv.expand(&scalars));
+            assert_eq!(
+                chain.evaluate_signed(&affine_bases, endomorphism),
+                msm.into_affine()
+            );
+        });
+        let plain = bos_coster_fast::build_chain(scalars.clone()).adds.len();
+        let halved = glv.build_chain(scalars, bos_coster_fast::build_chain);
+        assert!(halved.chain.adds.len() < plain);
+    }
+
+    #[test]
+    fn test_scalars() {
+        use scalar::Scalar;
+        let rng = &mut ark_std::test_rng();
+        // prefixes order like the scalars, and agree between representations
+        for _ in 0..1000 {
+            let (a, b) = (u128::rand(rng) >> (u8::rand(rng) % 128), u128::rand(rng));
+            let (a, b) = (a.min(b), a.max(b));
+            assert!(a.prefix() <= b.prefix());
+            #[cfg(feature = "bigint")]
+            assert_eq!(a.prefix(), BigUint::from(a).prefix());
+            let ark = scalar::ArkBigInt(Fr::from(a).into_repr());
+            assert_eq!(a.prefix(), ark.prefix());
+            if a < 1 << 56 {
+                assert_eq!(a.prefix() == b.prefix(), a == b);
+            }
+        }
+        let as_field = |t: &[u128]| -> Vec<Fr> { t.iter().map(|t| Fr::from(*t)).collect() };
+        type Builder<S> = fn(Vec<S>) -> VecAddChain;
+        fn builders<S: scalar::Scalar>() -> Vec<(&'static str, Builder<S>)> {
+            vec![
+                (
+                    "shallow",
+                    bos_coster::build_chain_scalars::<S, bos_coster::UseShallow>,
+                ),
+                (
+                    "many",
+                    bos_coster_many::build_chain_scalars::<S, bos_coster::UseShallow>,
+                ),
+                ("fast", bos_coster_fast::build_chain_scalars::<S>),
+                ("signed", bos_coster_signed::build_chain_scalars::<S>),
+                ("pippenger-3", |t| pippenger::build_chain_scalars(t, 3)),
+                ("straus-2", |t| straus::build_chain_scalars(t, 2)),
+            ]
+        }
+        for _ in 0..10 {
+            let target: Vec<u128> = (0..5).map(|_| u128::rand(rng) | 1).collect();
+            let small: Vec<u64> = target.iter().map(|t| *t as u64).collect();
+            let ark: Vec<_> = as_field(&target)
+                .iter()
+                .map(|f| scalar::ArkBigInt(f.into_repr()))
+                .collect();
+            for (name, builder) in builders::<u128>() {
+                println!("Running: {}", name);
+                check_chain(&builder(target.clone()), &as_field(&target));
+            }
+            for (name, builder) in builders::<u64>() {
+                println!("Running: {}", name);
+                let small_field: Vec<u128> = small.iter().map(|t| *t as u128).collect();
+                check_chain(&builder(small.clone()), &as_field(&small_field));
+            }
+            // every representation of the same target gives the same chain
+            let u128_chains: Vec<VecAddChain> = builders::<u128>()
+                .iter()
+                .map(|(_, b)| b(target.clone()))
+                .collect();
+            let ark_chains: Vec<VecAddChain> =
+                builders().iter().map(|(_, b)| b(ark.clone())).collect();
+            assert_eq!(u128_chains, ark_chains);
+            #[cfg(feature = "bigint")]
+            {
+                let big: Vec<BigUint> = target.iter().map(|t| BigUint::from(*t)).collect();
+                let big_chains: Vec<VecAddChain> = builders::<BigUint>()
+                    .iter()
+                    .map(|(_, b)| b(big.clone()))
+                    .collect();
+                assert_eq!(u128_chains, big_chains);
+            }
+            let targets = vec![target.clone(), target.iter().map(|t| t / 3).collect()];
+            let ark_targets = targets
+                .iter()
+                .map(|t| {
+                    as_field(t)
+                        .iter()
+                        .map(|f| scalar::ArkBigInt(f.into_repr()))
+                        .collect()
+                })
+                .collect();
+            let multi = multi::build_chain_scalars(targets.clone());
+            let fields: Vec<Vec<Fr>> = targets.iter().map(|t| as_field(t)).collect();
+            multi.validate(&fields).unwrap();
+            assert_eq!(multi, multi::build_chain_scalars(ark_targets));
+        }
+    }
+
+    #[test]
+    fn test_radix_heap() {
+        use super::heap::RadixHeap;
+        use ark_bls12_381::Fq;
+        use ark_ff::{BigInteger384, BigInteger768};
+        use bos_coster_fast::Entry;
+        use scalar::{ArkBigInt, Scalar};
+        use std::collections::BinaryHeap;
+        let power = |n: u32| {
+            let mut x = BigInteger768::from(1);
+            x.muln(n);
+            ArkBigInt(x)
+        };
+        // prefixes order like the scalars past 255 bits, and tie from 512 bits
+        let mut below = power(257);
+        below.sub_noborrow(&ArkBigInt::one());
+        assert!(below.prefix() < power(300).prefix());
+        assert!(power(510).prefix() < power(511).prefix());
+        assert_eq!(power(511).prefix(), power(700).prefix());
+
+        // a radix heap pops scalars of 252 to 381 bits in the same order as a binary heap
+        let rng = &mut ark_std::test_rng();
+        let mut radix = RadixHeap::new();
+        let mut binary = BinaryHeap::new();
+        for g in 0..1000 {
+            let mut x: BigInteger384 = Fq::rand(rng).into_repr();
+            x.divn(u32::rand(rng) % 130);
+            radix.push(Entry { x: ArkBigInt(x), g });
+            binary.push(Entry { x: ArkBigInt(x), g });
+        }
+        while let Some(expected) = binary.pop() {
+            radix.fill();
+            assert!(radix.remove(0) == expected);
+        }
+        assert_eq!(radix.len(), 0);
+    }
+
+    #[test]
+    fn test_exhaustive() {
+        assert!(exhaustive::shortest_chain(&[0, 0], 10).is_none());
+        assert!(exhaustive::shortest_chain(&[1, 0], 10).is_none());
+        assert_eq!(
+            exhaustive::shortest_chain(&[0, 1], 10).unwrap().adds,
+            vec![]
+        );
+        assert!(exhaustive::shortest_chain(&[37], 6).is_none());
+        for k in 1..200 {
+            let chain = exhaustive::shortest_chain(&[k], 20).unwrap();
+            assert_eq!(chain.adds.len(), scalar_chain::optimal(k).adds.len());
+        }
+
+        let rng = &mut ark_std::test_rng();
+        for dimension in 2..=4 {
+            for _ in 0..10 {
+                let target: Vec<u64> = (0..dimension).map(|_| u64::rand(rng) % 33).collect();
+                let field_target: Vec<Fr> = target.iter().map(|t| Fr::from(*t)).collect();
+                let shortest = match exhaustive::shortest_chain(&target, 30) {
+                    Some(chain) => chain,
+                    None => continue,
+                };
+                check_chain(&shortest, &field_target);
+                assert!(shortest.adds.len() >= bounds::lower_bound(&field_target));
+                // the shortest chain for (k, ..., k) is one for k after adding up the bases
+                let ks = vec![target[0].max(1); dimension];
+                assert_eq!(
+                    exhaustive::shortest_chain(&ks, 30).unwrap().adds.len(),
+                    scalar_chain::optimal(ks[0]).adds.len() + dimension - 1
+                );
+                each_builder(|builder| {
+                    let chain = builder(field_target.clone());
+                    if chain.subs.is_empty() {
+                        assert!(chain.adds.len() >= shortest.adds.len());
+                    }
+                });
+            }
+        }
+    }
+
+    #[test]
+    fn test_complete() {
+        let rng = &mut ark_std::test_rng();
+        let targets = vec![
+            vec![Fr::from(11u32), Fr::from(2u32)],
+            vec![Fr::from(2u32); 10],
+            (0..10).map(|_| Fr::rand(rng)).collect(),
+        ];
+        for target in targets {
+            each_builder(|builder| {
+                let complete = complete::make_complete::<Fr>(&builder(target.clone()));
+                check_chain(&complete.chain, &target);
+                let exceptions = exceptional::find_symbolic_exceptions::<Fr>(&complete.chain);
+                let doublings = exceptions
+                    .unwrap()
+                    .into_iter()
+                    .map(|e| {
+                        assert_eq!(e.kind, ExceptionKind::Doubling);
+                        e.step
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(doublings, complete.doubling_steps());
+                // removals save additions; only negating the output costs any
+                assert!([0, 2].contains(&(complete.extra_adds() + complete.removed as isize)));
+            });
+        }
+        // a - a is zero, so both it and the addition of it to b go
+        let chain = VecAddChain {
+            dimension: 2,
+            adds: vec![(0, 0), (1, 2), (3, 0)],
+            subs: vec![0],
+        };
+        let complete = complete::make_complete::<Fr>(&chain);
+        check_chain(&complete.chain, &[Fr::one(), Fr::one()]);
+        assert_eq!((complete.removed, complete.extra_adds()), (2, -2));
+    }
+
+    #[test]
+    fn test_multi() {
+        let rng = &mut ark_std::test_rng();
+        let mut targets: Vec<Vec<Fr>> = (0..3)
+            .map(|_| (0..20).map(|_| Fr::rand(rng)).collect())
+            .collect();
+        targets.push(targets[0].iter().zip(&targets[1]).map(|(a, b)| *a + b).collect());
+        targets.push(targets[0].clone());
+        let chain = multi::build_chain(targets.clone());
+        assert_eq!(chain.validate(&targets), Ok(()));
+        let bases: Vec<G1Projective> = (0..20).map(|_| G1Projective::rand(rng)).collect();
+        let outputs = chain.evaluate(&bases);
+        for (target, output) in targets.iter().zip(outputs) {
+            assert_eq!(output, naive_msm(target, &bases));
+        }
+        let separate: usize = targets
+            .iter()
+            .map(|t| bos_coster_fast::build_chain(t.clone()).adds.len())
+            .sum();
+        assert!(chain.chain.adds.len() < separate);
+        assert_eq!(
+            chain.validate(&targets[1..]),
+            Err(ChainError::OutputCountMismatch {
+                chain: 5,
+                targets: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_stream() {
+        let rng = &mut ark_std::test_rng();
+        for size in [1, 2, 10, 100] {
+            let target: Vec<Fr> = (0..size).map(|_| Fr::rand(rng)).collect();
+            let chain = bos_coster_fast::build_chain(target.clone());
+            let scalars: Vec<_> = target
+                .iter()
+                .map(|f| scalar::ArkBigInt(f.into_repr()))
+                .collect();
+            let steps = bos_coster_fast::Steps::new(scalars.clone());
+            assert_eq!(steps.count(), chain.adds.len());
+
+            let mut text = Vec::new();
+            let n_adds = stream::write_chain(scalars.clone(), &mut text).unwrap();
+            assert_eq!(n_adds, chain.adds.len());
+            let streamed: VecAddChain = String::from_utf8(text).unwrap().parse().unwrap();
+            assert_eq!(streamed, chain);
+
+            let path = std::env::temp_dir().join(format!("vac-test-stream-{}", size));
+            stream::spill_chain(scalars, &path).unwrap();
+            let spilled: VecAddChain = std::fs::read_to_string(&path).unwrap().parse().unwrap();
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(spilled, chain);
+        }
+        // a single unit target that is not the last basis vector needs a subtraction
+        let mut text = Vec::new();
+        stream::write_chain(vec![1u64, 0], &mut text).unwrap();
+        let chain: VecAddChain = String::from_utf8(text).unwrap().parse().unwrap();
+        check_chain(&chain, &[Fr::one(), Fr::zero()]);
+    }
+
+    #[test]
+    fn test_serialize() {
+        let rng = &mut ark_std::test_rng();
+        let target: Vec<Fr> = (0..10).map(|_| Fr::rand(rng)).collect();
+        each_builder(|builder| {
+            let chain = builder(target.clone());
+            assert_eq!(
+                VecAddChain::from_bytes(&chain.to_bytes().unwrap()),
+                Ok(chain.clone())
+            );
+            assert_eq!(chain.to_string().parse(), Ok(chain.clone()));
+            #[cfg(feature = "serde")]
+            {
+                let json = serde_json::to_string(&chain).unwrap();
+                assert_eq!(serde_json::from_str::<VecAddChain>(&json).unwrap(), chain);
+            }
+        });
+        let chain = VecAddChain {
+            dimension: 2,
+            adds: vec![(0, 0), (2, 1)],
+            subs: vec![1],
+        };
+        assert_eq!(chain.to_string(), "dimension 2\n2 = 0 + 0\n3 = 2 - 1\n");
+        let bytes = chain.to_bytes().unwrap();
+        assert_eq!(bytes, b"VAC\x01\x02\x02\x01\x01\x00\x01\x01\x01");
+        assert_eq!(
+            VecAddChain::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::UnexpectedEnd)
+        );
+        assert_eq!(
+            VecAddChain::from_bytes(b"VAD\x01"),
+            Err(DecodeError::BadMagic)
+        );
+        // an operand after its addition has no delta, so is not encoded
+        let forward = VecAddChain {
+            dimension: 2,
+            adds: vec![(0, 3), (2, 1)],
+            subs: vec![],
+        };
+        assert_eq!(
+            forward.to_bytes(),
+            Err(ChainError::ForwardReference { step: 0, label: 3 })
+        );
+        assert_eq!(
+            forward.to_string().parse::<VecAddChain>(),
+            Err(DecodeError::Invalid(ChainError::ForwardReference {
+                step: 0,
+                label: 3
+            }))
+        );
+        assert_eq!(
+            "dimension 2\n2 = 0 + 0\n4 = 2 - 1\n".parse::<VecAddChain>(),
+            Err(DecodeError::Parse { line: 3 })
+        );
+        assert_eq!(
+            "dimension 2\n2 = 0 + 3\n".parse::<VecAddChain>(),
+            Err(DecodeError::Invalid(ChainError::LabelOutOfRange {
+                step: 0,
+                label: 3
+            }))
+        );
+    }
+
+    #[test]
+    fn test_validate_errors() {
+        let chain = |adds: Vec<(usize, usize)>| VecAddChain {
+            dimension: 2,
+            adds,
+            subs: vec![],
+        };
+        let target = vec![Fr::from(2u32), Fr::from(1u32)];
+        assert_eq!(chain(vec![(0, 0), (1, 2)]).validate(&target), Ok(()));
+        assert_eq!(
+            chain(vec![(0, 0)]).validate(&[Fr::from(2u32)]),
+            Err(ChainError::DimensionMismatch { chain: 2, target: 1 })
+        );
+        assert_eq!(
+            chain(vec![(0, 0), (1, 4)]).validate(&target),
+            Err(ChainError::LabelOutOfRange { step: 1, label: 4 })
+        );
+        assert_eq!(
+            chain(vec![(0, 3), (1, 2)]).validate(&target),
+            Err(ChainError::ForwardReference { step: 0, label: 3 })
+        );
+        assert_eq!(
+            chain(vec![(0, 1), (1, 2)]).validate(&target),
+            Err(ChainError::Diverged { label: 3, coordinate: 0 })
+        );
+        assert_eq!(
+            chain(vec![(0, 1), (1, 2)]).validate_prefix(&[vec![Fr::from(1u32), Fr::from(0u32)]]),
+            Err(ChainError::Diverged { label: 2, coordinate: 1 })
+        );
+        assert_eq!(
+            chain(vec![(0, 0)]).validate_prefix(&[target.clone(), target.clone()]),
+            Err(ChainError::PrefixTooLong { prefix: 2, adds: 1 })
+        );
+        let signed = |subs| VecAddChain {
+            dimension: 2,
+            adds: vec![(0, 0), (2, 1)],
+            subs,
+        };
+        assert_eq!(signed(vec![1]).validate(&[Fr::from(2u32), -Fr::one()]), Ok(()));
+        assert_eq!(
+            signed(vec![2]).validate(&target),
+            Err(ChainError::InvalidSubtraction { step: 2 })
+        );
+        assert_eq!(
+            signed(vec![1, 0]).validate(&target),
+            Err(ChainError::InvalidSubtraction { step: 0 })
+        );
+    }
+
+    #[test]
+    fn test_optimize() {
+        // 2 = 0 + 1, 3 = 1 + 0 (duplicate), 4 = 0 + 0 (dead), 5 = 2 + 3
+        let chain = VecAddChain {
+            dimension: 2,
+            adds: vec![(0, 1), (1, 0), (0, 0), (2, 3)],
+            subs: vec![],
+        };
+        let expected = VecAddChain {
+            dimension: 2,
+            adds: vec![(0, 1), (2, 2)],
+            subs: vec![],
+        };
+        assert_eq!(optimize::optimize(&chain), expected);
+        // subtractions don't commute
+        let chain = VecAddChain {
+            dimension: 2,
+            adds: vec![(0, 1), (1, 0), (2, 3)],
+            subs: vec![0, 1],
+        };
+        assert_eq!(optimize::optimize(&chain), chain);
+
+        let rng = &mut ark_std::test_rng();
+        let target: Vec<Fr> = (0..20).map(|_| Fr::rand(rng)).collect();
+        each_builder(|builder| {
+            let chain = builder(target.clone());
+            let optimized = optimize::optimize(&chain);
+            check_chain(&optimized, &target);
+            assert!(optimized.adds.len() <= chain.adds.len());
+            assert_eq!(optimize::optimize(&optimized), optimized);
+        });
+    }
+
+    #[test]
+    fn test_scalar_chain() {
+        // the smallest scalars needing 0, 1, 2, ... additions
+        let smallest = [1u64, 2, 3, 5, 7, 11, 19, 29, 47, 71, 127, 191, 379, 607];
+        for (len, k) in smallest.iter().enumerate() {
+            let chain = scalar_chain::optimal(*k);
+            check_chain(&chain, &[Fr::from(*k)]);
+            assert_eq!(chain.adds.len(), len);
+            if len > 0 {
+                assert!(scalar_chain::optimal(k - 1).adds.len() < len);
+            }
+        }
+        let rng = &mut ark_std::test_rng();
+        for _ in 0..10 {
+            let k = Fr::rand(rng);
+            let bits = k.into_repr().to_bits_le();
+            let chain = scalar_chain::best_chain(&bits);
+            check_chain(&chain, &[k]);
+            let binary =
+                bits.iter().rposition(|b| *b).unwrap() + bits.iter().filter(|b| **b).count() - 1;
+            assert!(chain.adds.len() < binary);
+        }
+        #[cfg(feature = "bigint")]
+        for strategy in &[
+            scalar_chain::Strategy::Binary,
+            scalar_chain::Strategy::Dichotomic,
+        ] {
+            for k in (1..3000u64).step_by(7) {
+                let chain = scalar_chain::continued_fraction(&k.into(), *strategy);
+                check_chain(&chain, &[Fr::from(k)]);
+            }
+            // structured scalars are where continued fractions beat windows
+            let n = (num_bigint::BigUint::from(1u32) << 250u32) - 21u32;
+            let bits = Fr::from(n.clone()).into_repr().to_bits_le();
+            let chain = scalar_chain::continued_fraction(&n, *strategy);
+            check_chain(&chain, &[Fr::from(n)]);
+            assert!(chain.adds.len() < scalar_chain::sliding_window(&bits, 5).adds.len());
+        }
+        let chain = scalar_chain::build_chain(vec![Fr::from(1000u32)]);
+        assert_eq!(chain.adds.len(), scalar_chain::optimal(1000).adds.len());
+    }
+
+    #[test]
+    fn test_small_scalar_time() {
+        // some of the slowest scalars to search exhaustively; the budget keeps finalize quick
+        for k in &[607u32, 733, 767, 895, 1019] {
+            let start = std::time::Instant::now();
+            let chain = bos_coster_fast::build_chain(vec![Fr::from(*k)]);
+            assert!(start.elapsed() < std::time::Duration::from_millis(100));
+            check_chain(&chain, &[Fr::from(*k)]);
+            let bits: Vec<bool> = (0..10).map(|i| (k >> i) & 1 == 1).collect();
+            assert!(chain.adds.len() <= scalar_chain::sliding_window(&bits, 2).adds.len());
+        }
+    }
+
+    #[test]
+    fn test_schedule() {
+        let chain = VecAddChain {
+            dimension: 2,
+            adds: vec![(0, 0), (1, 1), (2, 3), (1, 0), (4, 5)],
+            subs: vec![3],
+        };
+        assert_eq!(chain.label_depths(), vec![0, 0, 1, 1, 2, 1, 3]);
+        assert_eq!(chain.depth(), 3);
+        assert_eq!(chain.levels(), vec![vec![0, 1, 3], vec![2], vec![4]]);
+
+        let rng = &mut ark_std::test_rng();
+        let scalars: Vec<Fr> = (0..20).map(|_| Fr::rand(rng)).collect();
+        each_builder(|builder| {
+            let chain = builder(scalars.clone());
+            let depths = chain.label_depths();
+            let levels = chain.levels();
+            assert_eq!(levels.len(), chain.depth());
+            assert_eq!(levels.iter().map(|l| l.len()).sum::<usize>(), chain.adds.len());
+            for (i, level) in levels.iter().enumerate() {
+                for &step in level {
+                    let (a, b) = chain.adds[step];
+                    assert!(depths[a] <= i && depths[b] <= i);
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn test_pippenger_window() {
+        let rng = &mut ark_std::test_rng();
+        let target: Vec<Fr> = (0..3).map(|_| Fr::rand(rng)).collect();
+        let widest = pippenger::build_chain(target.clone(), pippenger::MAX_WINDOW);
+        check_chain(&widest, &target);
+        // windows past the scalar width (or 64 bits) are clamped, not overflowed
+        for window in [64, 300] {
+            assert_eq!(pippenger::build_chain(target.clone(), window), widest);
+        }
+    }
+
+    #[test]
+    fn test_depth() {
+        let rng = &mut ark_std::test_rng();
+        let target: Vec<Fr> = (0..20).map(|_| Fr::rand(rng)).collect();
+        let bits = Fr::size_in_bits();
+        each_builder(|builder| {
+            let chain = builder(target.clone());
+            let balanced = depth::rebalance(&chain);
+            check_chain(&balanced, &target);
+            assert_eq!(balanced.adds.len(), chain.adds.len());
+            assert!(balanced.depth() <= chain.depth());
+        });
+        let chunk = depth::best_chunk(bits);
+        let low = depth::build_low_depth_auto(target.clone());
+        assert!(low.depth() <= bits + chunk + bits / chunk + 6);
+        assert!(low.adds.len() <= 20 * bits / 2 + 2 * bits + 100);
+        // the additions-versus-depth curve, from the Fast chain down to the low-depth one
+        let mut curve: Vec<(usize, usize)> = depth::tradeoff(&target)
+            .iter()
+            .map(|chain| {
+                check_chain(chain, &target);
+                (chain.depth(), chain.adds.len())
+            })
+            .collect();
+        assert_eq!(curve[0].1, bos_coster_fast::build_chain(target.clone()).adds.len());
+        // its ends are its extremes: the Fast chain is the shortest, the low-depth one the shallowest
+        assert_eq!(curve.iter().map(|c| c.1).min(), Some(curve[0].1));
+        assert_eq!(curve.iter().map(|c| c.0).min(), Some(curve.last().unwrap().0));
+        curve.sort();
+        assert!(3 * curve[0].0 < curve.last().unwrap().0);
+        let mut adds = usize::MAX;
+        for (max_depth, _) in curve {
+            let bounded = depth::build_chain(target.clone(), max_depth).unwrap();
+            check_chain(&bounded, &target);
+            assert!(bounded.depth() <= max_depth);
+            assert!(bounded.adds.len() <= adds);
+            adds = bounded.adds.len();
+        }
+        assert!(depth::build_chain(target, bits / 2).is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "parallel")]
+    fn test_bos_coster_par() {
+        use super::bos_coster_par;
+        let rng = &mut ark_std::test_rng();
+        for n in 1..50u32 {
+            for target in [
+                vec![Fr::one(); n as usize],
+                (1..=n).map(Fr::from).collect(),
+                vec![Fr::from(n)],
+            ] {
+                check_chain(&bos_coster_par::build_chain(target.clone()), &target);
+            }
+        }
+        for size in [2, 3, 10, 100, 1000] {
+            let target: Vec<Fr> = (0..size).map(|_| Fr::rand(rng)).collect();
+            let chain = bos_coster_par::build_chain(target.clone());
+            check_chain(&chain, &target);
+            // the same additions as the sequential builder, in another order
+            let fast = bos_coster_fast::build_chain(target.clone());
+            assert_eq!(chain.adds.len(), fast.adds.len());
+            // and the chain doesn't depend on the number of threads
+            for threads in [1, 4] {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .unwrap();
+                assert_eq!(
+                    pool.install(|| bos_coster_par::build_chain(target.clone())),
+                    chain
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "parallel")]
+    fn test_evaluate_par() {
+        let rng = &mut ark_std::test_rng();
+        let scalars: Vec<Fr> = (0..20).map(|_| Fr::rand(rng)).collect();
+        let bases: Vec<G1Projective> = (0..20).map(|_| G1Projective::rand(rng)).collect();
+        each_builder(|builder| {
+            let chain = builder(scalars.clone());
+            assert_eq!(chain.evaluate_par(&bases), chain.evaluate_signed(&bases));
+        });
+    }
+
     #[test]
     fn test_incr_ed_on_bls12_381() {
         test_incr::<ark_ed_on_bls12_381::Fr>();
@@ -157,4 +1156,29 @@ mod tests {
         test_rand::<ark_ed_on_bls12_381::Fr>(10, 10);
         test_rand::<ark_ed_on_bls12_381::Fr>(1000, 1);
     }
+
+    #[test]
+    #[cfg(feature = "r1cs")]
+    fn test_r1cs_ed_on_bls12_381() {
+        use ark_ed_on_bls12_381::{constraints::EdwardsVar, EdwardsProjective, Fq};
+        use ark_r1cs_std::{alloc::AllocVar, R1CSVar};
+        use ark_relations::r1cs::ConstraintSystem;
+        let rng = &mut ark_std::test_rng();
+        let n = 4;
+        let target: Vec<ark_ed_on_bls12_381::Fr> = (0..n).map(|_| UniformRand::rand(rng)).collect();
+        let bases: Vec<EdwardsProjective> = (0..n).map(|_| UniformRand::rand(rng)).collect();
+        each_builder(|builder| {
+            let chain = builder(target.clone());
+            let cs = ConstraintSystem::<Fq>::new_ref();
+            let vars: Vec<EdwardsVar> = bases
+                .iter()
+                .map(|b| EdwardsVar::new_witness(cs.clone(), || Ok(*b)).unwrap())
+                .collect();
+            let out = chain.evaluate_var(&vars);
+            assert_eq!(out.value().unwrap(), chain.evaluate_signed(&bases));
+            assert!(cs.is_satisfied().unwrap());
+            let count = super::r1cs::count_constraints::<_, _, EdwardsVar>(&chain).unwrap();
+            assert!(count > 0);
+        });
+    }
 }
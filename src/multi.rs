@@ -0,0 +1,226 @@
+//! Chains with several outputs, sharing work between them.
+//!
+//! [build_chain] runs Bos-Coster on each target in turn, over a shared set of terms. Each term has
+//! a coefficient in every target, so when one target's step creates `h = g1 + g2`, every other
+//! target with coefficients `a, b` on `g1, g2` moves `min(a, b)` of them onto `h` for free
+//! (similarly for doublings).
+//!
+//! Independent random targets have little in common, so they gain little; targets with shared
+//! structure (repeated scalars, or targets that are combinations of others) gain a lot.
+use super::scalar::{ArkBigInt, Scalar};
+use super::sparse::{self, SparseVec};
+use super::{ChainError, SignedGroup, VecAddChain};
+use ark_ff::{Field, PrimeField};
+use std::collections::BinaryHeap;
+
+/// A vector addition chain with several outputs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultiVecAddChain {
+    /// The additions. Its result (last term) is not special.
+    pub chain: VecAddChain,
+    /// The label of each output.
+    pub outputs: Vec<usize>,
+}
+
+impl MultiVecAddChain {
+    /// Check that output `i` computes `targets[i]`, for each `i`.
+    pub fn validate<F: Field>(&self, targets: &[Vec<F>]) -> Result<(), ChainError> {
+        if self.outputs.len() != targets.len() {
+            return Err(ChainError::OutputCountMismatch {
+                chain: self.outputs.len(),
+                targets: targets.len(),
+            });
+        }
+        let n_labels = self.chain.dimension + self.chain.adds.len();
+        for (output, label) in self.outputs.iter().enumerate() {
+            if *label >= n_labels {
+                return Err(ChainError::OutputOutOfRange {
+                    output,
+                    label: *label,
+                });
+            }
+        }
+        if let Some(t) = targets.iter().find(|t| t.len() != self.chain.dimension) {
+            return Err(ChainError::DimensionMismatch {
+                chain: self.chain.dimension,
+                target: t.len(),
+            });
+        }
+        let check = |label: usize, term: &SparseVec<F>| {
+            for (output, target) in self.outputs.iter().zip(targets) {
+                if *output == label {
+                    if let Some(coordinate) = sparse::first_difference(term, target) {
+                        return Err(ChainError::Diverged { label, coordinate });
+                    }
+                }
+            }
+            Ok(())
+        };
+        let bases: Vec<SparseVec<F>> = (0..self.chain.dimension).map(sparse::basis).collect();
+        for (i, b) in bases.iter().enumerate() {
+            check(i, b)?;
+        }
+        self.chain.sparse_fold::<F, _>(bases, |step, _, _, sum| {
+            check(self.chain.dimension + step, sum)
+        })?;
+        Ok(())
+    }
+
+    /// Evaluate the chain on `bases`, returning the value of each output.
+    ///
+    /// Panics if `bases` does not have length `dimension`, or if the chain is malformed.
+    pub fn evaluate<G: SignedGroup>(&self, bases: &[G]) -> Vec<G> {
+        assert_eq!(bases.len(), self.chain.dimension);
+        let mut terms: Vec<G> = bases.to_vec();
+        for (step, (a, b)) in self.chain.adds.iter().enumerate() {
+            let sum = if self.chain.is_sub(step) {
+                terms[*a].group_sub(&terms[*b])
+            } else {
+                terms[*a].group_add(&terms[*b])
+            };
+            terms.push(sum);
+        }
+        self.outputs.iter().map(|o| terms[*o].clone()).collect()
+    }
+}
+
+struct Term<B> {
+    label: usize,
+    /// The coefficient of this term in each target.
+    coeffs: Vec<B>,
+}
+
+struct State<B> {
+    chain: VecAddChain,
+    terms: Vec<Term<B>>,
+    /// For each target, `(coefficient, term index)` pairs.
+    ///
+    /// Lazily updated: an entry is stale if its coefficient is no longer the term's.
+    heaps: Vec<BinaryHeap<(B, usize)>>,
+}
+
+impl<B: Scalar> State<B> {
+    fn new(targets: Vec<Vec<B>>) -> Self {
+        let dimension = targets.first().map(|t| t.len()).unwrap_or(0);
+        assert!(targets.iter().all(|t| t.len() == dimension));
+        let mut this = State {
+            chain: VecAddChain {
+                dimension,
+                adds: vec![],
+                subs: vec![],
+            },
+            terms: Vec::new(),
+            heaps: targets.iter().map(|_| BinaryHeap::new()).collect(),
+        };
+        for i in 0..dimension {
+            let coeffs = targets.iter().map(|t| t[i].clone()).collect();
+            this.new_term(i, coeffs);
+        }
+        this
+    }
+    fn new_term(&mut self, label: usize, coeffs: Vec<B>) -> usize {
+        let t = self.terms.len();
+        for (j, c) in coeffs.iter().enumerate() {
+            if !c.is_zero() {
+                self.heaps[j].push((c.clone(), t));
+            }
+        }
+        self.terms.push(Term { label, coeffs });
+        t
+    }
+    fn set_coeff(&mut self, t: usize, j: usize, c: B) {
+        if self.terms[t].coeffs[j] != c {
+            self.terms[t].coeffs[j] = c.clone();
+            if !c.is_zero() {
+                self.heaps[j].push((c, t));
+            }
+        }
+    }
+    /// Pop the term with the largest coefficient in target `j`.
+    fn pop(&mut self, j: usize) -> Option<(B, usize)> {
+        while let Some((c, t)) = self.heaps[j].pop() {
+            if self.terms[t].coeffs[j] == c {
+                return Some((c, t));
+            }
+        }
+        None
+    }
+    /// Double term `t`, moving half of each target's coefficient onto the result.
+    fn double(&mut self, t: usize) {
+        let label = self
+            .chain
+            .push(self.terms[t].label, self.terms[t].label, false);
+        let mut coeffs = self.terms[t].coeffs.clone();
+        for (j, c) in coeffs.iter_mut().enumerate() {
+            let rest = if c.is_odd() { B::one() } else { B::zero() };
+            self.set_coeff(t, j, rest);
+            c.div2();
+        }
+        self.new_term(label, coeffs);
+    }
+    /// Add terms `t1` and `t2`, moving the common part of each target's coefficients onto the
+    /// result.
+    fn add(&mut self, t1: usize, t2: usize) {
+        let label = self
+            .chain
+            .push(self.terms[t1].label, self.terms[t2].label, false);
+        let n_targets = self.heaps.len();
+        let mut coeffs = Vec::with_capacity(n_targets);
+        for j in 0..n_targets {
+            let (mut a, mut b) = (
+                self.terms[t1].coeffs[j].clone(),
+                self.terms[t2].coeffs[j].clone(),
+            );
+            let m = a.clone().min(b.clone());
+            if !m.is_zero() {
+                a.sub_noborrow(&m);
+                b.sub_noborrow(&m);
+                self.set_coeff(t1, j, a);
+                self.set_coeff(t2, j, b);
+            }
+            coeffs.push(m);
+        }
+        self.new_term(label, coeffs);
+    }
+    /// Run Bos-Coster on target `j`, returning the label of its output.
+    fn build(&mut self, j: usize) -> usize {
+        let one = B::one();
+        loop {
+            let (x1, t1) = self.pop(j).expect("zero target");
+            let second = self.pop(j);
+            let mut half_x1 = x1.clone();
+            half_x1.div2();
+            match second {
+                None if x1 == one => return self.terms[t1].label,
+                Some((x2, t2)) if half_x1 <= x2 => self.add(t1, t2),
+                _ => {
+                    if let Some(entry) = second {
+                        self.heaps[j].push(entry);
+                    }
+                    self.double(t1);
+                }
+            }
+        }
+    }
+}
+
+/// Build one chain that computes every target.
+pub fn build_chain<F: PrimeField>(targets: Vec<Vec<F>>) -> MultiVecAddChain {
+    build_chain_scalars(
+        targets
+            .into_iter()
+            .map(|t| t.into_iter().map(|f| ArkBigInt(f.into_repr())).collect())
+            .collect(),
+    )
+}
+
+/// [build_chain], for any [Scalar]s.
+pub fn build_chain_scalars<S: Scalar>(targets: Vec<Vec<S>>) -> MultiVecAddChain {
+    let mut state = State::new(targets);
+    let outputs = (0..state.heaps.len()).map(|j| state.build(j)).collect();
+    MultiVecAddChain {
+        chain: state.chain,
+        outputs,
+    }
+}
@@ -0,0 +1,67 @@
+//! A peephole pass over finished chains.
+//!
+//! Builders that don't hash-cons their terms (like [crate::bos_coster_fast]) can emit the same
+//! addition twice, or additions that nothing uses. [optimize] removes both.
+use super::VecAddChain;
+use std::collections::HashMap;
+
+/// Merge duplicate additions, remove additions that the output does not depend on, and compact the
+/// labels (which stay in topological order).
+///
+/// Additions `a + b` and `b + a` are duplicates; subtractions are only duplicates of identical
+/// subtractions. The result computes the same output, with no more additions.
+///
+/// Panics if the chain is malformed.
+pub fn optimize(chain: &VecAddChain) -> VecAddChain {
+    chain.validate_structure().expect("malformed chain");
+    let dimension = chain.dimension;
+
+    // merge duplicates, mapping each label to the first label with the same addition
+    let mut deduped = VecAddChain {
+        dimension,
+        adds: vec![],
+        subs: vec![],
+    };
+    let mut labels: Vec<usize> = (0..dimension).collect();
+    let mut seen: HashMap<(usize, usize, bool), usize> = HashMap::new();
+    for (step, (a, b)) in chain.adds.iter().enumerate() {
+        let (a, b, sub) = (labels[*a], labels[*b], chain.is_sub(step));
+        let key = if sub || a < b {
+            (a, b, sub)
+        } else {
+            (b, a, sub)
+        };
+        let label = *seen.entry(key).or_insert_with(|| deduped.push(a, b, sub));
+        labels.push(label);
+    }
+    if let Some(output) = labels.last() {
+        deduped.set_output(*output);
+    }
+
+    // remove dead additions, working back from the output
+    let n_labels = dimension + deduped.adds.len();
+    let mut live = vec![false; n_labels];
+    if let Some(l) = live.last_mut() {
+        *l = true;
+    }
+    for (step, (a, b)) in deduped.adds.iter().enumerate().rev() {
+        if live[dimension + step] {
+            live[*a] = true;
+            live[*b] = true;
+        }
+    }
+    let mut out = VecAddChain {
+        dimension,
+        adds: vec![],
+        subs: vec![],
+    };
+    let mut labels: Vec<usize> = (0..dimension).collect();
+    for (step, (a, b)) in deduped.adds.iter().enumerate() {
+        labels.push(if live[dimension + step] {
+            out.push(labels[*a], labels[*b], deduped.is_sub(step))
+        } else {
+            usize::MAX
+        });
+    }
+    out
+}
@@ -0,0 +1,93 @@
+//! The bucket method (Pippenger's algorithm), as a vector addition chain.
+//!
+//! Each scalar is split into `window`-bit digits. For each window, from the most significant, every
+//! base is added into the bucket for its digit, and the buckets are combined as
+//! `sum_k k B_k` using running sums. The windows are combined by doubling.
+use super::scalar::{self, ArkBigInt, Scalar};
+use super::VecAddChain;
+use ark_ff::PrimeField;
+
+/// The largest window used. Larger windows would need `2^window` buckets.
+pub const MAX_WINDOW: usize = 20;
+
+/// Add two optional terms, where `None` is zero.
+fn add(chain: &mut VecAddChain, a: Option<usize>, b: Option<usize>) -> Option<usize> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(chain.push(a, b, false)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+/// The approximate number of additions that the bucket method takes.
+fn cost(dimension: usize, bits: usize, window: usize) -> usize {
+    let windows = bits.div_ceil(window);
+    windows * (dimension + 2 * ((1 << window) - 1)) + bits
+}
+
+/// The window that minimizes the number of additions for `dimension` scalars of `bits` bits.
+pub fn best_window(dimension: usize, bits: usize) -> usize {
+    (1..=bits.min(MAX_WINDOW))
+        .min_by_key(|w| cost(dimension, bits, *w))
+        .unwrap_or(1)
+}
+
+/// Build a chain for `target` with the bucket method.
+///
+/// `window` is clamped to the scalar width and to [MAX_WINDOW]. Panics if it is zero.
+pub fn build_chain<F: PrimeField>(target: Vec<F>, window: usize) -> VecAddChain {
+    build_chain_scalars(
+        target
+            .into_iter()
+            .map(|f| ArkBigInt(f.into_repr()))
+            .collect(),
+        window,
+    )
+}
+
+/// [build_chain], for any [Scalar]s.
+pub fn build_chain_scalars<S: Scalar>(target: Vec<S>, window: usize) -> VecAddChain {
+    assert!(window > 0);
+    let scalars: Vec<Vec<bool>> = target.iter().map(scalar::to_bits_le).collect();
+    let bits = scalars.iter().map(|s| s.len()).max().unwrap_or(0);
+    let window = window.min(bits.max(1)).min(MAX_WINDOW);
+    let digit = |s: &[bool], w: usize| {
+        (0..window)
+            .filter(|i| s.get(w * window + i).copied().unwrap_or(false))
+            .map(|i| 1 << i)
+            .sum::<usize>()
+    };
+    let mut chain = VecAddChain {
+        dimension: target.len(),
+        adds: vec![],
+        subs: vec![],
+    };
+    let mut acc = None;
+    for w in (0..bits.div_ceil(window)).rev() {
+        for _ in 0..window {
+            acc = acc.map(|a| chain.push(a, a, false));
+        }
+        let mut buckets = vec![None; (1 << window) - 1];
+        for (i, s) in scalars.iter().enumerate() {
+            let d = digit(s, w);
+            if d != 0 {
+                buckets[d - 1] = add(&mut chain, buckets[d - 1], Some(i));
+            }
+        }
+        let mut running = None;
+        let mut sum = None;
+        for bucket in buckets.into_iter().rev() {
+            running = add(&mut chain, running, bucket);
+            sum = add(&mut chain, sum, running);
+        }
+        acc = add(&mut chain, acc, sum);
+    }
+    chain.set_output(acc.expect("zero target"));
+    chain
+}
+
+/// [build_chain], with the window from [best_window].
+pub fn build_chain_auto<F: PrimeField>(target: Vec<F>) -> VecAddChain {
+    let window = best_window(target.len(), F::size_in_bits());
+    build_chain(target, window)
+}
@@ -0,0 +1,52 @@
+//! Evaluating a [VecAddChain] inside an R1CS circuit, with arkworks `r1cs-std` curve variables.
+//!
+//! Requires the `r1cs` feature. Twisted Edwards variables (e.g.
+//! `ark_ed_on_bls12_381::constraints::EdwardsVar`) are the intended target: their addition law is
+//! complete, so the chain needs no exception handling (see [crate::complete]).
+use super::VecAddChain;
+use ark_ec::ProjectiveCurve;
+use ark_ff::{Field, PrimeField};
+use ark_r1cs_std::groups::CurveVar;
+use ark_relations::r1cs::{ConstraintSystem, SynthesisError};
+
+impl VecAddChain {
+    /// Evaluate the chain on the allocated `bases`, emitting an addition (or subtraction) gadget
+    /// for each step, and return the last term.
+    ///
+    /// Panics under the same conditions as [VecAddChain::evaluate_signed].
+    pub fn evaluate_var<C, CF, V>(&self, bases: &[V]) -> V
+    where
+        C: ProjectiveCurve,
+        CF: Field,
+        V: CurveVar<C, CF>,
+    {
+        self.evaluate_with(bases, |a, b, sub| {
+            if sub {
+                a.clone() - b
+            } else {
+                a.clone() + b
+            }
+        })
+    }
+}
+
+/// Count the constraints that [VecAddChain::evaluate_var] emits for `chain` with curve variable
+/// `V`.
+///
+/// The bases are allocated as witnesses in a fresh constraint system; the constraints from their
+/// allocation (e.g. subgroup checks) are not counted.
+pub fn count_constraints<C, CF, V>(chain: &VecAddChain) -> Result<usize, SynthesisError>
+where
+    C: ProjectiveCurve,
+    CF: PrimeField,
+    V: CurveVar<C, CF>,
+{
+    let cs = ConstraintSystem::<CF>::new_ref();
+    let g = C::prime_subgroup_generator();
+    let bases = (0..chain.dimension)
+        .map(|_| V::new_witness(cs.clone(), || Ok(g)))
+        .collect::<Result<Vec<V>, _>>()?;
+    let before = cs.num_constraints();
+    chain.evaluate_var(&bases);
+    Ok(cs.num_constraints() - before)
+}
@@ -0,0 +1,165 @@
+//! The scalars that the Bos-Coster builders work with.
+//!
+//! The builders only halve, add, subtract and compare scalars, so they take anything implementing
+//! [Scalar]: `u64`, `u128`, `BigUint` (with the `bigint` feature), and arkworks big integers
+//! (wrapped in [ArkBigInt]). Scalars modulo a group order that isn't an arkworks field can be given
+//! as their least residues.
+use ark_ff::BigInteger;
+#[cfg(feature = "bigint")]
+use num_bigint::BigUint;
+
+/// An unsigned integer, with the operations the Bos-Coster builders use.
+pub trait Scalar: Clone + Ord {
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn is_zero(&self) -> bool;
+    fn is_odd(&self) -> bool;
+    /// Halve, rounding down.
+    fn div2(&mut self);
+    /// Subtract `other`, returning whether that borrowed (in which case `self` is unspecified).
+    fn sub_noborrow(&mut self, other: &Self) -> bool;
+    /// Add `other`, returning whether that carried (in which case `self` is unspecified).
+    fn add_nocarry(&mut self, other: &Self) -> bool;
+    /// A key that orders like `self`, except that it may tie unequal scalars. The builders compare
+    /// keys first, so a key that rarely ties saves time; the default always ties.
+    fn prefix(&self) -> u64 {
+        0
+    }
+}
+
+/// Bit lengths from here on don't fit in a [Scalar::prefix].
+const PREFIX_MAX_BITS: u64 = 1 << 9;
+
+/// The [Scalar::prefix] of the integer with little-endian `limbs`: its bit length in 9 bits, then
+/// the 55 bits after its leading one. Integers of [PREFIX_MAX_BITS] or more bits all get the
+/// largest key.
+fn limbs_prefix(limbs: &[u64]) -> u64 {
+    let top = match limbs.iter().rposition(|l| *l != 0) {
+        Some(top) => top,
+        None => return 0,
+    };
+    let zeros = limbs[top].leading_zeros();
+    let mut window = limbs[top] << zeros;
+    if zeros > 0 && top > 0 {
+        window |= limbs[top - 1] >> (64 - zeros);
+    }
+    let bits = 64 * top as u64 + 64 - zeros as u64;
+    if bits >= PREFIX_MAX_BITS {
+        return u64::MAX;
+    }
+    (bits << 55) | ((window << 1) >> 9)
+}
+
+macro_rules! primitive_scalar {
+    ($($t:ty),*) => {
+        $(
+            impl Scalar for $t {
+                fn zero() -> Self {
+                    0
+                }
+                fn one() -> Self {
+                    1
+                }
+                fn is_zero(&self) -> bool {
+                    *self == 0
+                }
+                fn is_odd(&self) -> bool {
+                    *self & 1 == 1
+                }
+                fn div2(&mut self) {
+                    *self >>= 1;
+                }
+                fn sub_noborrow(&mut self, other: &Self) -> bool {
+                    let (diff, borrow) = self.overflowing_sub(*other);
+                    *self = diff;
+                    borrow
+                }
+                fn add_nocarry(&mut self, other: &Self) -> bool {
+                    let (sum, carry) = self.overflowing_add(*other);
+                    *self = sum;
+                    carry
+                }
+                fn prefix(&self) -> u64 {
+                    limbs_prefix(&[*self as u64, (*self as u128 >> 64) as u64])
+                }
+            }
+        )*
+    };
+}
+
+primitive_scalar!(u64, u128);
+
+#[cfg(feature = "bigint")]
+impl Scalar for BigUint {
+    fn zero() -> Self {
+        BigUint::from(0u32)
+    }
+    fn one() -> Self {
+        BigUint::from(1u32)
+    }
+    fn is_zero(&self) -> bool {
+        self.bits() == 0
+    }
+    fn is_odd(&self) -> bool {
+        self.bit(0)
+    }
+    fn div2(&mut self) {
+        *self >>= 1u32;
+    }
+    fn sub_noborrow(&mut self, other: &Self) -> bool {
+        if *other > *self {
+            return true;
+        }
+        *self -= other;
+        false
+    }
+    fn add_nocarry(&mut self, other: &Self) -> bool {
+        *self += other;
+        false
+    }
+    fn prefix(&self) -> u64 {
+        limbs_prefix(&self.to_u64_digits())
+    }
+}
+
+/// An arkworks big integer, as a [Scalar].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ArkBigInt<B>(pub B);
+
+impl<B: BigInteger> Scalar for ArkBigInt<B> {
+    fn zero() -> Self {
+        ArkBigInt(B::from(0))
+    }
+    fn one() -> Self {
+        ArkBigInt(B::from(1))
+    }
+    fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+    fn is_odd(&self) -> bool {
+        self.0.is_odd()
+    }
+    fn div2(&mut self) {
+        self.0.div2();
+    }
+    fn sub_noborrow(&mut self, other: &Self) -> bool {
+        self.0.sub_noborrow(&other.0)
+    }
+    fn add_nocarry(&mut self, other: &Self) -> bool {
+        self.0.add_nocarry(&other.0)
+    }
+    fn prefix(&self) -> u64 {
+        limbs_prefix(self.0.as_ref())
+    }
+}
+
+/// The little-endian bits of `s`, up to its highest set bit.
+pub(crate) fn to_bits_le<S: Scalar>(s: &S) -> Vec<bool> {
+    let mut s = s.clone();
+    let mut bits = Vec::new();
+    while !s.is_zero() {
+        bits.push(s.is_odd());
+        s.div2();
+    }
+    bits
+}
@@ -0,0 +1,297 @@
+//! Addition chains for scalars: [VecAddChain]s of dimension one.
+//!
+//! There are three generators: [optimal] (exhaustive, for small scalars), [sliding_window], and
+//! `continued_fraction` (Bergeron, Berstel, Brlek and Duboc; with the `bigint` feature).
+//! [best_chain] takes the shortest of them; the Bos-Coster builders use it to multiply their last
+//! remaining term by its scalar, and [build_chain] is a builder for one-dimensional targets.
+use super::{straus, VecAddChain};
+use ark_ff::{BigInteger, PrimeField};
+#[cfg(feature = "bigint")]
+use num_bigint::BigUint;
+#[cfg(feature = "bigint")]
+use std::collections::HashMap;
+
+/// Scalars of at most this many bits get a shortest chain, from [optimal_within], when the search
+/// fits in [SEARCH_BUDGET].
+pub const OPTIMAL_BITS: usize = 10;
+
+/// How many partial chains [best_chain] lets [optimal_within] visit before falling back to
+/// windows; about a millisecond.
+pub const SEARCH_BUDGET: usize = 1 << 14;
+
+/// The largest window [best_chain] tries.
+const MAX_WINDOW: usize = 8;
+
+/// A sliding-window chain for the scalar with little-endian `bits`.
+///
+/// Panics if the scalar is zero.
+pub fn sliding_window(bits: &[bool], window: usize) -> VecAddChain {
+    straus::build_chain_bits(&[bits.to_vec()], window)
+}
+
+/// A shortest star chain for `k` (each addition uses the previous value), by iterative deepening.
+/// Star chains are shortest addition chains for all `k < 12509`.
+///
+/// Takes time exponential in the length of the chain, so only suitable for small `k`.
+pub fn optimal(k: u64) -> VecAddChain {
+    optimal_within(k, usize::MAX).unwrap()
+}
+
+/// [optimal], giving up after visiting `budget` partial chains.
+pub fn optimal_within(k: u64, mut budget: usize) -> Option<VecAddChain> {
+    assert!(k > 0, "zero scalar");
+    let mut values = vec![1u64];
+    // no chain of length l reaches past 2^l
+    let mut len = 63 - k.leading_zeros() as usize;
+    while !extend(k, len, &mut values, &mut budget)? {
+        len += 1;
+    }
+    let mut chain = VecAddChain {
+        dimension: 1,
+        adds: vec![],
+        subs: vec![],
+    };
+    for i in 1..values.len() {
+        let (a, b) = (0..i)
+            .flat_map(|a| (a..i).map(move |b| (a, b)))
+            .find(|(a, b)| values[*a] + values[*b] == values[i])
+            .unwrap();
+        chain.push(a, b, false);
+    }
+    Some(chain)
+}
+
+/// Extend the increasing star chain `values` to end in `k`, with at most `len` additions in total.
+///
+/// `None` if that takes more than `budget` calls.
+fn extend(k: u64, len: usize, values: &mut Vec<u64>, budget: &mut usize) -> Option<bool> {
+    *budget = budget.checked_sub(1)?;
+    let last = *values.last().unwrap();
+    if last == k {
+        return Some(true);
+    }
+    let steps = len + 1 - values.len();
+    if steps == 0 || last << steps < k {
+        return Some(false);
+    }
+    if last << steps == k {
+        values.extend((1..=steps).map(|i| last << i));
+        return Some(true);
+    }
+    // short of doubling all the way, some step adds at most the value before it
+    if values.len() > 1 && (last + values[values.len() - 2]) << (steps - 1) < k {
+        return Some(false);
+    }
+    let sums: Vec<u64> = values
+        .iter()
+        .rev()
+        .map(|a| last + a)
+        .filter(|s| *s <= k)
+        .collect();
+    for s in sums {
+        values.push(s);
+        if extend(k, len, values, budget)? {
+            return Some(true);
+        }
+        values.pop();
+    }
+    Some(false)
+}
+
+/// How [continued_fraction] picks the divisor `k` of `n`.
+#[cfg(feature = "bigint")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// `k = n mod 2^(bits/2)`
+    Binary,
+    /// `k = n / 2^(bits/2)`
+    Dichotomic,
+}
+
+#[cfg(feature = "bigint")]
+impl Strategy {
+    fn divisor(self, n: &BigUint) -> BigUint {
+        let half = n.bits() / 2;
+        match self {
+            Strategy::Binary => n % (BigUint::from(1u32) << half),
+            Strategy::Dichotomic => n >> half,
+        }
+    }
+}
+
+/// A continued-fraction chain for `n`.
+///
+/// To reach `n`, pick `k` (by `strategy`), write `n = qk + r`, reach `k` through `r` recursively,
+/// multiply by `q`, and add `r`. Small scalars get [optimal_within] chains, and scalars where a
+/// [sliding_window] chain is shorter get that instead. Best on scalars with structure, like
+/// `2^250 - 21`; on random scalars it rarely beats the windows alone.
+///
+/// Panics if `n` is zero.
+#[cfg(feature = "bigint")]
+pub fn continued_fraction(n: &BigUint, strategy: Strategy) -> VecAddChain {
+    assert!(n.bits() > 0, "zero scalar");
+    let mut values = min_chain(n, strategy);
+    values.sort();
+    values.dedup();
+    from_values(&values)
+}
+
+/// An increasing chain of values ending in `n`.
+#[cfg(feature = "bigint")]
+fn min_chain(n: &BigUint, strategy: Strategy) -> Vec<BigUint> {
+    if n.bits() as usize <= OPTIMAL_BITS {
+        let k = n.iter_u64_digits().next().unwrap();
+        if let Some(chain) = optimal_within(k, SEARCH_BUDGET) {
+            let mut values = vec![1u64];
+            for (a, b) in &chain.adds {
+                values.push(values[*a] + values[*b]);
+            }
+            return values.into_iter().map(BigUint::from).collect();
+        }
+    }
+    if n.count_ones() == 1 {
+        return (0..n.bits()).map(|i| BigUint::from(1u32) << i).collect();
+    }
+    let k = strategy.divisor(n);
+    let fraction = match k.bits() {
+        // only the binary strategy gives k < 2, when n is 0 or 1 mod 2^(bits/2)
+        0 => {
+            let twos = n.trailing_zeros().unwrap();
+            times(
+                &min_chain(&(n >> twos), strategy),
+                &min_chain(&(BigUint::from(1u32) << twos), strategy),
+            )
+        }
+        1 => {
+            let mut values = min_chain(&(n - 1u32), strategy);
+            values.push(n.clone());
+            values
+        }
+        _ => chain_through(n, &k, strategy),
+    };
+    // parts of n may have no useful structure, in which case windows are better
+    let bits = to_bits(n);
+    let window = (1..=MAX_WINDOW)
+        .map(|w| sliding_window(&bits, w))
+        .min_by_key(|chain| chain.adds.len())
+        .unwrap();
+    if window.adds.len() + 1 < fraction.len() {
+        values(&window)
+    } else {
+        fraction
+    }
+}
+
+/// The values of a scalar chain.
+#[cfg(feature = "bigint")]
+fn values(chain: &VecAddChain) -> Vec<BigUint> {
+    let mut values = vec![BigUint::from(1u32)];
+    for (a, b) in &chain.adds {
+        let sum = &values[*a] + &values[*b];
+        values.push(sum);
+    }
+    values
+}
+
+/// An increasing chain ending in `n` that contains `k`.
+#[cfg(feature = "bigint")]
+fn chain_through(n: &BigUint, k: &BigUint, strategy: Strategy) -> Vec<BigUint> {
+    let (q, r) = (n / k, n % k);
+    if r.bits() == 0 {
+        times(&min_chain(k, strategy), &min_chain(&q, strategy))
+    } else {
+        let mut values = times(&chain_through(k, &r, strategy), &min_chain(&q, strategy));
+        values.push(n.clone());
+        values
+    }
+}
+
+/// The chain `a`, followed by the chain `b` scaled by the end of `a`.
+#[cfg(feature = "bigint")]
+fn times(a: &[BigUint], b: &[BigUint]) -> Vec<BigUint> {
+    let last = a.last().unwrap();
+    a.iter()
+        .cloned()
+        .chain(b[1..].iter().map(|v| v * last))
+        .collect()
+}
+
+/// The chain with the given increasing `values`, the first of which is one.
+#[cfg(feature = "bigint")]
+fn from_values(values: &[BigUint]) -> VecAddChain {
+    let index: HashMap<&BigUint, usize> = values.iter().enumerate().map(|(i, v)| (v, i)).collect();
+    let mut chain = VecAddChain {
+        dimension: 1,
+        adds: vec![],
+        subs: vec![],
+    };
+    for (i, v) in values.iter().enumerate().skip(1) {
+        let (a, b) = values[..i]
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(a, x)| index.get(&(v - x)).map(|b| (a, *b)))
+            .expect("not an addition chain");
+        chain.push(a, b, false);
+    }
+    chain
+}
+
+/// The little-endian bits of a [BigUint].
+#[cfg(feature = "bigint")]
+fn to_bits(n: &BigUint) -> Vec<bool> {
+    (0..n.bits()).map(|i| n.bit(i)).collect()
+}
+
+/// Pack little-endian bits into a [BigUint].
+#[cfg(feature = "bigint")]
+fn to_biguint(bits: &[bool]) -> BigUint {
+    let bytes: Vec<u8> = bits
+        .chunks(8)
+        .map(|byte| byte.iter().rev().fold(0, |acc, b| (acc << 1) | *b as u8))
+        .collect();
+    BigUint::from_bytes_le(&bytes)
+}
+
+/// The shortest chain for the scalar with little-endian `bits`: from [optimal_within] if the
+/// scalar has at most [OPTIMAL_BITS] bits and the search fits in [SEARCH_BUDGET], and otherwise
+/// the shortest of [sliding_window] and, with the `bigint` feature, `continued_fraction`.
+///
+/// Panics if the scalar is zero.
+pub fn best_chain(bits: &[bool]) -> VecAddChain {
+    let n_bits = bits.iter().rposition(|b| *b).expect("zero scalar") + 1;
+    if n_bits <= OPTIMAL_BITS {
+        let k = (0..n_bits).filter(|i| bits[*i]).map(|i| 1 << i).sum();
+        if let Some(chain) = optimal_within(k, SEARCH_BUDGET) {
+            return chain;
+        }
+    }
+    let chains = (1..=MAX_WINDOW).map(|w| sliding_window(bits, w));
+    #[cfg(feature = "bigint")]
+    let chains = {
+        let n = to_biguint(bits);
+        chains.chain(
+            vec![Strategy::Binary, Strategy::Dichotomic]
+                .into_iter()
+                .map(move |s| continued_fraction(&n, s)),
+        )
+    };
+    chains.min_by_key(|chain| chain.adds.len()).unwrap()
+}
+
+/// A chain for a one-dimensional target, from [best_chain].
+pub fn build_chain<F: PrimeField>(target: Vec<F>) -> VecAddChain {
+    assert_eq!(target.len(), 1, "not a one-dimensional target");
+    best_chain(&target[0].into_repr().to_bits_le())
+}
+
+/// Evaluate the scalar `chain` on `x`, where `add` adds two terms.
+pub(crate) fn apply<T: Clone>(chain: &VecAddChain, x: T, mut add: impl FnMut(&T, &T) -> T) -> T {
+    assert_eq!(chain.dimension, 1);
+    let mut terms = vec![x];
+    for (a, b) in &chain.adds {
+        let sum = add(&terms[*a], &terms[*b]);
+        terms.push(sum);
+    }
+    terms.pop().unwrap()
+}
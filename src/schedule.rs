@@ -0,0 +1,71 @@
+//! Depth and level scheduling of chains, and (with the `parallel` feature) parallel evaluation.
+//!
+//! The depth of a label is the length of the longest path of additions from a base to it; bases
+//! have depth zero. Steps of equal depth never depend on each other, so each level can be
+//! evaluated concurrently.
+use super::VecAddChain;
+#[cfg(feature = "parallel")]
+use super::SignedGroup;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+impl VecAddChain {
+    /// The depth of every label.
+    ///
+    /// Panics if the chain is malformed (see [VecAddChain::validate_structure]).
+    pub fn label_depths(&self) -> Vec<usize> {
+        let mut depths = vec![0; self.dimension];
+        depths.reserve(self.adds.len());
+        for (a, b) in &self.adds {
+            let d = 1 + std::cmp::max(depths[*a], depths[*b]);
+            depths.push(d);
+        }
+        depths
+    }
+
+    /// The critical-path depth of the chain: the depth of its deepest label.
+    pub fn depth(&self) -> usize {
+        self.label_depths().into_iter().max().unwrap_or(0)
+    }
+
+    /// The steps grouped into levels of independent operations.
+    ///
+    /// Level `i` holds, in order, the steps of depth `i + 1`; their operands are all bases or
+    /// results of earlier levels.
+    pub fn levels(&self) -> Vec<Vec<usize>> {
+        let depths = self.label_depths();
+        let mut levels = vec![Vec::new(); self.depth()];
+        for step in 0..self.adds.len() {
+            levels[depths[self.dimension + step] - 1].push(step);
+        }
+        levels
+    }
+
+    /// Like [VecAddChain::evaluate_signed], but evaluates each of [VecAddChain::levels]
+    /// concurrently.
+    #[cfg(feature = "parallel")]
+    pub fn evaluate_par<G: SignedGroup + Send + Sync>(&self, bases: &[G]) -> G {
+        assert_eq!(bases.len(), self.dimension);
+        let mut terms: Vec<Option<G>> = bases.iter().cloned().map(Some).collect();
+        terms.resize(self.dimension + self.adds.len(), None);
+        for level in self.levels() {
+            let sums: Vec<G> = level
+                .par_iter()
+                .map(|&step| {
+                    let (a, b) = self.adds[step];
+                    let x = terms[a].as_ref().expect("forward reference");
+                    let y = terms[b].as_ref().expect("forward reference");
+                    if self.is_sub(step) {
+                        x.group_sub(y)
+                    } else {
+                        x.group_add(y)
+                    }
+                })
+                .collect();
+            for (step, sum) in level.into_iter().zip(sums) {
+                terms[self.dimension + step] = Some(sum);
+            }
+        }
+        terms.pop().flatten().expect("empty chain")
+    }
+}
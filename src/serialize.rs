@@ -0,0 +1,208 @@
+//! Binary and text encodings of [VecAddChain].
+//!
+//! The binary encoding is compact and versioned. After the magic bytes `VAC` and a version byte,
+//! it holds LEB128 varints: the dimension, the number of additions, then for each addition with
+//! label `c`, the deltas `c - 1 - a` and `c - 1 - b` of its operands (small, since operands tend
+//! to be recent), and finally the number of subtractions and the gaps between their steps.
+//!
+//! The text encoding has a `dimension n` line, then one `c = a + b` (or `c = a - b`) line per
+//! addition. Blank lines and lines starting with `#` are ignored.
+use super::{ChainError, VecAddChain};
+use std::fmt;
+use std::str::FromStr;
+
+const MAGIC: &[u8] = b"VAC";
+const VERSION: u8 = 1;
+
+/// A reason that a chain could not be decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input does not start with the magic bytes.
+    BadMagic,
+    /// The input has an encoding version this crate does not know.
+    UnsupportedVersion(u8),
+    /// The input ended in the middle of the chain.
+    UnexpectedEnd,
+    /// A varint does not fit in a `usize`.
+    VarintOverflow,
+    /// The input continues after the end of the chain.
+    TrailingBytes,
+    /// An operand of addition `step` is encoded as a label before 0.
+    BadDelta { step: usize },
+    /// Line `line` of the text encoding is not valid.
+    Parse { line: usize },
+    /// The decoded chain is malformed.
+    Invalid(ChainError),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::BadMagic => write!(f, "not a chain: bad magic bytes"),
+            DecodeError::UnsupportedVersion(v) => write!(f, "unsupported version {}", v),
+            DecodeError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            DecodeError::VarintOverflow => write!(f, "varint overflows usize"),
+            DecodeError::TrailingBytes => write!(f, "trailing bytes after chain"),
+            DecodeError::BadDelta { step } => write!(f, "addition {} has a bad operand", step),
+            DecodeError::Parse { line } => write!(f, "cannot parse line {}", line),
+            DecodeError::Invalid(e) => write!(f, "invalid chain: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<ChainError> for DecodeError {
+    fn from(e: ChainError) -> Self {
+        DecodeError::Invalid(e)
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut x: usize) {
+    while x >= 0x80 {
+        out.push((x as u8) | 0x80);
+        x >>= 7;
+    }
+    out.push(x as u8);
+}
+
+fn read_varint(input: &mut &[u8]) -> Result<usize, DecodeError> {
+    let mut x = 0usize;
+    let mut shift = 0;
+    loop {
+        let (byte, rest) = input.split_first().ok_or(DecodeError::UnexpectedEnd)?;
+        *input = rest;
+        let bits = (byte & 0x7f) as usize;
+        if shift >= usize::BITS || (bits << shift) >> shift != bits {
+            return Err(DecodeError::VarintOverflow);
+        }
+        x |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok(x);
+        }
+        shift += 7;
+    }
+}
+
+impl VecAddChain {
+    /// Encode the chain in the binary format.
+    ///
+    /// Fails if the chain is malformed (see [VecAddChain::validate_structure]), since operands are
+    /// encoded relative to the label of their addition.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ChainError> {
+        self.validate_structure()?;
+        let mut out = MAGIC.to_vec();
+        out.push(VERSION);
+        write_varint(&mut out, self.dimension);
+        write_varint(&mut out, self.adds.len());
+        for (i, (a, b)) in self.adds.iter().enumerate() {
+            let c = self.dimension + i;
+            write_varint(&mut out, c - 1 - a);
+            write_varint(&mut out, c - 1 - b);
+        }
+        write_varint(&mut out, self.subs.len());
+        let mut prev = 0;
+        for s in &self.subs {
+            write_varint(&mut out, s - prev);
+            prev = *s;
+        }
+        Ok(out)
+    }
+
+    /// Decode a chain in the binary format.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut input = bytes.strip_prefix(MAGIC).ok_or(DecodeError::BadMagic)?;
+        let (version, rest) = input.split_first().ok_or(DecodeError::UnexpectedEnd)?;
+        if *version != VERSION {
+            return Err(DecodeError::UnsupportedVersion(*version));
+        }
+        input = rest;
+        let dimension = read_varint(&mut input)?;
+        let n_adds = read_varint(&mut input)?;
+        let mut adds = Vec::with_capacity(n_adds.min(input.len()));
+        for i in 0..n_adds {
+            let c = dimension + i;
+            let operand = |input: &mut &[u8]| {
+                let delta = read_varint(input)?;
+                c.checked_sub(1)
+                    .and_then(|c| c.checked_sub(delta))
+                    .ok_or(DecodeError::BadDelta { step: i })
+            };
+            let a = operand(&mut input)?;
+            let b = operand(&mut input)?;
+            adds.push((a, b));
+        }
+        let n_subs = read_varint(&mut input)?;
+        let mut subs = Vec::with_capacity(n_subs.min(input.len()));
+        let mut prev = 0usize;
+        for _ in 0..n_subs {
+            prev = prev
+                .checked_add(read_varint(&mut input)?)
+                .ok_or(DecodeError::VarintOverflow)?;
+            subs.push(prev);
+        }
+        if !input.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+        let chain = VecAddChain {
+            dimension,
+            adds,
+            subs,
+        };
+        chain.validate_structure()?;
+        Ok(chain)
+    }
+}
+
+impl fmt::Display for VecAddChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "dimension {}", self.dimension)?;
+        for (i, (a, b)) in self.adds.iter().enumerate() {
+            let op = if self.is_sub(i) { '-' } else { '+' };
+            writeln!(f, "{} = {} {} {}", self.dimension + i, a, op, b)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for VecAddChain {
+    type Err = DecodeError;
+    /// Parse a chain in the text format.
+    fn from_str(s: &str) -> Result<Self, DecodeError> {
+        let mut lines = s
+            .lines()
+            .enumerate()
+            .map(|(i, l)| (i + 1, l.trim()))
+            .filter(|(_, l)| !l.is_empty() && !l.starts_with('#'));
+        let (line, header) = lines.next().ok_or(DecodeError::UnexpectedEnd)?;
+        let dimension = header
+            .strip_prefix("dimension ")
+            .and_then(|d| d.trim().parse().ok())
+            .ok_or(DecodeError::Parse { line })?;
+        let mut chain = VecAddChain {
+            dimension,
+            adds: vec![],
+            subs: vec![],
+        };
+        for (line, text) in lines {
+            let err = DecodeError::Parse { line };
+            let words: Vec<&str> = text.split_whitespace().collect();
+            let (c, a, op, b) = match words.as_slice() {
+                [c, "=", a, op, b] => (c, a, op, b),
+                _ => return Err(err),
+            };
+            let label = |w: &str| w.parse::<usize>().map_err(|_| err.clone());
+            let sub = match *op {
+                "+" => false,
+                "-" => true,
+                _ => return Err(err),
+            };
+            if label(c)? != chain.dimension + chain.adds.len() {
+                return Err(err);
+            }
+            chain.push(label(a)?, label(b)?, sub);
+        }
+        chain.validate_structure()?;
+        Ok(chain)
+    }
+}
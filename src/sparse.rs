@@ -0,0 +1,73 @@
+use ark_ff::Field;
+
+/// A sparse vector over `F`.
+///
+/// A list of `(coordinate, coefficient)` pairs, sorted by coordinate, with no zero coefficients.
+pub type SparseVec<F> = Vec<(usize, F)>;
+
+pub fn basis<F: Field>(i: usize) -> SparseVec<F> {
+    vec![(i, F::one())]
+}
+
+/// `a + b`, or `a - b` if `sub`.
+pub fn add_signed<F: Field>(a: &[(usize, F)], b: &[(usize, F)], sub: bool) -> SparseVec<F> {
+    let sign = |f: F| if sub { -f } else { f };
+    let mut out = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let (ai, af) = a[i];
+        let (bj, bf) = b[j];
+        if ai < bj {
+            out.push((ai, af));
+            i += 1;
+        } else if bj < ai {
+            out.push((bj, sign(bf)));
+            j += 1;
+        } else {
+            let s = af + sign(bf);
+            if !s.is_zero() {
+                out.push((ai, s));
+            }
+            i += 1;
+            j += 1;
+        }
+    }
+    out.extend_from_slice(&a[i..]);
+    out.extend(b[j..].iter().map(|(j, f)| (*j, sign(*f))));
+    out
+}
+
+/// The first coordinate at which `a` and the dense vector `dense` differ, if any.
+pub fn first_difference<F: Field>(a: &[(usize, F)], dense: &[F]) -> Option<usize> {
+    let mut entries = a.iter().peekable();
+    for (i, f) in dense.iter().enumerate() {
+        let here = match entries.peek() {
+            Some((j, g)) if *j == i => {
+                entries.next();
+                *g
+            }
+            _ => F::zero(),
+        };
+        if here != *f {
+            return Some(i);
+        }
+    }
+    entries.next().map(|(j, _)| *j)
+}
+
+pub fn from_dense<F: Field>(dense: &[F]) -> SparseVec<F> {
+    dense
+        .iter()
+        .enumerate()
+        .filter(|(_, f)| !f.is_zero())
+        .map(|(i, f)| (i, *f))
+        .collect()
+}
+
+/// Whether `a == -b`.
+pub fn is_opposite<F: Field>(a: &[(usize, F)], b: &[(usize, F)]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|((i, f), (j, g))| i == j && (*f + g).is_zero())
+}
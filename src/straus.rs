@@ -0,0 +1,117 @@
+//! Straus's method (interleaved sliding windows), as a vector addition chain.
+//!
+//! Good for small dimensions. Each base gets a table of its odd multiples, up to `2^window - 1`,
+//! and each scalar is split into sliding-window digits. Then a single accumulator walks down the
+//! bit positions, doubling once per position (shared by all scalars) and adding in table entries.
+use super::scalar::{self, ArkBigInt, Scalar};
+use super::VecAddChain;
+use ark_ff::PrimeField;
+
+/// The sliding-window digits of `bits` (little-endian): `(position, odd digit)` pairs, with
+/// digits less than `2^window`, most significant first.
+fn digits(bits: &[bool], window: usize) -> Vec<(usize, usize)> {
+    let mut out = Vec::new();
+    let mut i = bits.len();
+    while i > 0 {
+        if !bits[i - 1] {
+            i -= 1;
+            continue;
+        }
+        // the window is bits[lo..i], trimmed to end in a set bit
+        let mut lo = i.saturating_sub(window);
+        while !bits[lo] {
+            lo += 1;
+        }
+        let digit = (lo..i).filter(|j| bits[*j]).map(|j| 1 << (j - lo)).sum();
+        out.push((lo, digit));
+        i = lo;
+    }
+    out
+}
+
+/// The window that minimizes the approximate additions per scalar, for scalars of `bits` bits.
+pub fn best_window(bits: usize) -> usize {
+    // a table of 2^(w-1) entries, plus about bits/(w+1) digits
+    (1..=bits.clamp(1, 16))
+        .min_by_key(|w| (1 << (w - 1)) + bits / (w + 1))
+        .unwrap()
+}
+
+/// Build a chain for `target` with Straus's method, using windows of `window` bits.
+///
+/// Panics if `window` is zero.
+pub fn build_chain<F: PrimeField>(target: Vec<F>, window: usize) -> VecAddChain {
+    build_chain_scalars(
+        target
+            .into_iter()
+            .map(|f| ArkBigInt(f.into_repr()))
+            .collect(),
+        window,
+    )
+}
+
+/// [build_chain], for any [Scalar]s.
+pub fn build_chain_scalars<S: Scalar>(target: Vec<S>, window: usize) -> VecAddChain {
+    let scalars: Vec<Vec<bool>> = target.iter().map(scalar::to_bits_le).collect();
+    build_chain_bits(&scalars, window)
+}
+
+/// [build_chain], for scalars given as little-endian bits.
+pub(crate) fn build_chain_bits(scalars: &[Vec<bool>], window: usize) -> VecAddChain {
+    assert!(window > 0);
+    let mut chain = VecAddChain {
+        dimension: scalars.len(),
+        adds: vec![],
+        subs: vec![],
+    };
+    let digits: Vec<Vec<(usize, usize)>> = scalars.iter().map(|s| digits(s, window)).collect();
+    // tables[i][j] is the label of (2j + 1) times base i
+    let tables: Vec<Vec<usize>> = digits
+        .iter()
+        .enumerate()
+        .map(|(i, ds)| {
+            let max = ds.iter().map(|(_, d)| *d).max().unwrap_or(0);
+            let mut table = vec![i];
+            if max > 1 {
+                let double = chain.push(i, i, false);
+                while 2 * table.len() < max {
+                    let next = chain.push(*table.last().unwrap(), double, false);
+                    table.push(next);
+                }
+            }
+            table
+        })
+        .collect();
+    let mut by_position: Vec<(usize, usize, usize)> = digits
+        .iter()
+        .enumerate()
+        .flat_map(|(i, ds)| ds.iter().map(move |(pos, d)| (*pos, i, *d)))
+        .collect();
+    by_position.sort_unstable_by(|a, b| b.cmp(a));
+    let mut acc: Option<usize> = None;
+    let mut acc_pos = by_position.first().map(|(pos, _, _)| *pos).unwrap_or(0);
+    for (pos, i, d) in by_position {
+        let term = tables[i][d / 2];
+        acc = Some(match acc {
+            Some(mut a) => {
+                for _ in pos..acc_pos {
+                    a = chain.push(a, a, false);
+                }
+                chain.push(a, term, false)
+            }
+            None => term,
+        });
+        acc_pos = pos;
+    }
+    let mut acc = acc.expect("zero target");
+    for _ in 0..acc_pos {
+        acc = chain.push(acc, acc, false);
+    }
+    chain.set_output(acc);
+    chain
+}
+
+/// [build_chain], with the window from [best_window].
+pub fn build_chain_auto<F: PrimeField>(target: Vec<F>) -> VecAddChain {
+    build_chain(target, best_window(F::size_in_bits()))
+}
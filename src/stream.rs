@@ -0,0 +1,35 @@
+//! Building chains that are too large to hold in memory.
+//!
+//! [write_chain] runs the Fast Bos-Coster builder ([bos_coster_fast::Steps]) and writes each
+//! addition as soon as it is made, so memory is bounded by the builder's heap rather than by the
+//! length of the chain. The output is the text encoding (see [crate::serialize]), since the binary
+//! one needs the number of additions up front. [spill_chain] writes it to a file.
+use super::bos_coster_fast::Steps;
+use super::scalar::Scalar;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Build a chain for `target`, writing it to `out` in the text encoding as it is built.
+///
+/// Returns the number of additions.
+pub fn write_chain<S: Scalar, W: Write>(target: Vec<S>, mut out: W) -> io::Result<usize> {
+    let steps = Steps::new(target);
+    let dimension = steps.dimension();
+    writeln!(out, "dimension {}", dimension)?;
+    let mut n_adds = 0;
+    for (a, b, sub) in steps {
+        let op = if sub { '-' } else { '+' };
+        writeln!(out, "{} = {} {} {}", dimension + n_adds, a, op, b)?;
+        n_adds += 1;
+    }
+    out.flush()?;
+    Ok(n_adds)
+}
+
+/// Build a chain for `target`, spilling it to the file at `path` as it is built.
+///
+/// Returns the number of additions. Read the chain back with [str::parse].
+pub fn spill_chain<S: Scalar>(target: Vec<S>, path: impl AsRef<Path>) -> io::Result<usize> {
+    write_chain(target, BufWriter::new(File::create(path)?))
+}
@@ -0,0 +1,189 @@
+use super::sparse::{self, SparseVec};
+use super::VecAddChain;
+use ark_ff::Field;
+use std::fmt;
+
+/// A reason that a [VecAddChain] is malformed, or does not compute what it should.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainError {
+    /// The chain's dimension differs from the target's.
+    DimensionMismatch { chain: usize, target: usize },
+    /// Addition `step` refers to a label that does not exist in the chain.
+    LabelOutOfRange { step: usize, label: usize },
+    /// Addition `step` refers to a label that is not computed until `step` or later.
+    ForwardReference { step: usize, label: usize },
+    /// A prefix of `prefix` vectors was given, but the chain only has `adds` additions.
+    PrefixTooLong { prefix: usize, adds: usize },
+    /// `subs` lists `step`, but it is not an addition, or is out of order.
+    InvalidSubtraction { step: usize },
+    /// The term with label `label` differs from the expected vector, first at `coordinate`.
+    Diverged { label: usize, coordinate: usize },
+    /// A multi-output chain has `chain` outputs, but there are `targets` targets.
+    OutputCountMismatch { chain: usize, targets: usize },
+    /// Output `output` is a label that does not exist in the chain.
+    OutputOutOfRange { output: usize, label: usize },
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainError::DimensionMismatch { chain, target } => write!(
+                f,
+                "chain has dimension {}, but target has dimension {}",
+                chain, target
+            ),
+            ChainError::LabelOutOfRange { step, label } => {
+                write!(
+                    f,
+                    "addition {} uses label {}, which is out of range",
+                    step, label
+                )
+            }
+            ChainError::ForwardReference { step, label } => write!(
+                f,
+                "addition {} uses label {}, which is not yet computed",
+                step, label
+            ),
+            ChainError::PrefixTooLong { prefix, adds } => write!(
+                f,
+                "prefix has {} vectors, but chain has only {} additions",
+                prefix, adds
+            ),
+            ChainError::InvalidSubtraction { step } => {
+                write!(f, "subtraction {} is out of order, or out of range", step)
+            }
+            ChainError::Diverged { label, coordinate } => write!(
+                f,
+                "term {} differs from the expected vector at coordinate {}",
+                label, coordinate
+            ),
+            ChainError::OutputCountMismatch { chain, targets } => write!(
+                f,
+                "chain has {} outputs, but there are {} targets",
+                chain, targets
+            ),
+            ChainError::OutputOutOfRange { output, label } => {
+                write!(
+                    f,
+                    "output {} is label {}, which is out of range",
+                    output, label
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ChainError {}
+
+impl VecAddChain {
+    /// Check that every addition only uses labels computed before it, and that `subs` is sorted
+    /// and in range.
+    pub fn validate_structure(&self) -> Result<(), ChainError> {
+        for (i, step) in self.subs.iter().enumerate() {
+            if *step >= self.adds.len() || (i > 0 && self.subs[i - 1] >= *step) {
+                return Err(ChainError::InvalidSubtraction { step: *step });
+            }
+        }
+        let n_labels = self.dimension + self.adds.len();
+        for (step, (a, b)) in self.adds.iter().enumerate() {
+            for &label in &[*a, *b] {
+                if label >= n_labels {
+                    return Err(ChainError::LabelOutOfRange { step, label });
+                }
+                if label >= self.dimension + step {
+                    return Err(ChainError::ForwardReference { step, label });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that this chain computes `target`.
+    ///
+    /// Terms are tracked sparsely, so this takes time proportional to the chain length times the
+    /// size of the terms' supports.
+    pub fn validate<F: Field>(&self, target: &[F]) -> Result<(), ChainError> {
+        if self.dimension != target.len() {
+            return Err(ChainError::DimensionMismatch {
+                chain: self.dimension,
+                target: target.len(),
+            });
+        }
+        let bases = (0..self.dimension).map(sparse::basis).collect();
+        if let Some(output) = self.sparse_fold::<F, _>(bases, |_, _, _, _| Ok(()))? {
+            if let Some(coordinate) = sparse::first_difference(&output, target) {
+                return Err(ChainError::Diverged {
+                    label: self.dimension + self.adds.len() - 1,
+                    coordinate,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the first `prefix.len()` additions compute the vectors in `prefix`, in order.
+    ///
+    /// Reports the first addition whose result diverges.
+    pub fn validate_prefix<F: Field>(&self, prefix: &[Vec<F>]) -> Result<(), ChainError> {
+        if let Some(expected) = prefix.iter().find(|v| v.len() != self.dimension) {
+            return Err(ChainError::DimensionMismatch {
+                chain: self.dimension,
+                target: expected.len(),
+            });
+        }
+        if prefix.len() > self.adds.len() {
+            return Err(ChainError::PrefixTooLong {
+                prefix: prefix.len(),
+                adds: self.adds.len(),
+            });
+        }
+        let bases = (0..self.dimension).map(sparse::basis).collect();
+        self.sparse_fold::<F, _>(bases, |step, _, _, sum| match prefix.get(step) {
+            Some(expected) => match sparse::first_difference(sum, expected) {
+                Some(coordinate) => Err(ChainError::Diverged {
+                    label: self.dimension + step,
+                    coordinate,
+                }),
+                None => Ok(()),
+            },
+            None => Ok(()),
+        })?;
+        Ok(())
+    }
+
+    /// Run the chain over sparse vectors, starting from `bases`.
+    ///
+    /// Calls `visit(step, a, b, a ± b)` for each addition, and returns the last term.
+    /// Terms are dropped once no later addition uses them, so memory is bounded by the terms live
+    /// at any one step, not by the whole chain.
+    pub(crate) fn sparse_fold<F: Field, V>(
+        &self,
+        bases: Vec<SparseVec<F>>,
+        mut visit: V,
+    ) -> Result<Option<SparseVec<F>>, ChainError>
+    where
+        V: FnMut(usize, &SparseVec<F>, &SparseVec<F>, &SparseVec<F>) -> Result<(), ChainError>,
+    {
+        assert_eq!(bases.len(), self.dimension);
+        self.validate_structure()?;
+        let n_labels = self.dimension + self.adds.len();
+        let mut last_use = vec![usize::MAX; n_labels];
+        for (step, (a, b)) in self.adds.iter().enumerate() {
+            last_use[*a] = step;
+            last_use[*b] = step;
+        }
+        let mut terms = bases;
+        terms.reserve(self.adds.len());
+        for (step, (a, b)) in self.adds.iter().enumerate() {
+            let sum = sparse::add_signed(&terms[*a], &terms[*b], self.is_sub(step));
+            visit(step, &terms[*a], &terms[*b], &sum)?;
+            for &label in &[*a, *b] {
+                if last_use[label] == step {
+                    terms[label] = Vec::new();
+                }
+            }
+            terms.push(sum);
+        }
+        Ok(terms.pop())
+    }
+}